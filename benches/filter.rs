@@ -0,0 +1,108 @@
+#[macro_use]
+extern crate bencher;
+
+use std::sync::Once;
+
+use bencher::Bencher;
+use rand::random;
+
+use sstable::filter::{BlockedBloomPolicy, BloomPolicy, FilterPolicy, XorPolicy};
+
+const BITS_PER_KEY: u32 = 10;
+const KEYS: usize = 100000;
+
+fn random_key() -> Vec<u8> {
+    (0..16).map(|_| random::<u8>() % 26 + 65).collect()
+}
+
+/// Returns the concatenated keys and their offsets, as expected by `create_filter()`.
+fn make_keys(n: usize) -> (Vec<u8>, Vec<usize>) {
+    let mut keys = Vec::with_capacity(n * 16);
+    let mut offsets = Vec::with_capacity(n);
+    for _ in 0..n {
+        offsets.push(keys.len());
+        keys.extend_from_slice(&random_key());
+    }
+    (keys, offsets)
+}
+
+/// Prints the false-positive rate of `policy` for a filter over `KEYS` keys.
+fn report_fp_rate(policy: &dyn FilterPolicy) {
+    let (keys, offsets) = make_keys(KEYS);
+    let filter = policy.create_filter(&keys, &offsets);
+
+    let tests = 100000;
+    // Lowercase keys are never part of the filter.
+    let positives = (0..tests)
+        .filter(|i| policy.key_may_match(format!("absent{:010}", i).as_bytes(), &filter))
+        .count();
+    println!(
+        "{}: {} bytes, false-positive rate {:.4}%",
+        policy.name(),
+        filter.len(),
+        100. * positives as f64 / tests as f64
+    );
+}
+
+fn bench_create(b: &mut Bencher, policy: &dyn FilterPolicy) {
+    let (keys, offsets) = make_keys(KEYS);
+    b.iter(|| policy.create_filter(&keys, &offsets));
+}
+
+// The bench functions are called several times; report each false-positive rate only once.
+static BLOOM_FP_RATE: Once = Once::new();
+static BLOCKED_BLOOM_FP_RATE: Once = Once::new();
+static XOR_FP_RATE: Once = Once::new();
+
+fn bench_probe(b: &mut Bencher, policy: &dyn FilterPolicy, report: &Once) {
+    report.call_once(|| report_fp_rate(policy));
+
+    let (keys, offsets) = make_keys(KEYS);
+    let filter = policy.create_filter(&keys, &offsets);
+    let probes: Vec<Vec<u8>> = (0..1000).map(|_| random_key()).collect();
+    b.iter(|| {
+        probes
+            .iter()
+            .filter(|k| policy.key_may_match(k, &filter))
+            .count()
+    });
+}
+
+fn bench_bloom_create(b: &mut Bencher) {
+    bench_create(b, &BloomPolicy::new(BITS_PER_KEY));
+}
+
+fn bench_blocked_bloom_create(b: &mut Bencher) {
+    bench_create(b, &BlockedBloomPolicy::new(BITS_PER_KEY));
+}
+
+fn bench_bloom_probe(b: &mut Bencher) {
+    bench_probe(b, &BloomPolicy::new(BITS_PER_KEY), &BLOOM_FP_RATE);
+}
+
+fn bench_blocked_bloom_probe(b: &mut Bencher) {
+    bench_probe(
+        b,
+        &BlockedBloomPolicy::new(BITS_PER_KEY),
+        &BLOCKED_BLOOM_FP_RATE,
+    );
+}
+
+fn bench_xor_create(b: &mut Bencher) {
+    bench_create(b, &XorPolicy::new());
+}
+
+fn bench_xor_probe(b: &mut Bencher) {
+    bench_probe(b, &XorPolicy::new(), &XOR_FP_RATE);
+}
+
+benchmark_group!(
+    benches,
+    bench_bloom_create,
+    bench_blocked_bloom_create,
+    bench_xor_create,
+    bench_bloom_probe,
+    bench_blocked_bloom_probe,
+    bench_xor_probe
+);
+benchmark_main!(benches);
@@ -18,13 +18,14 @@ fn random_string(n: usize) -> String {
 
 fn write_tmp_table(entries: usize) {
     let mut v = vec![(String::new(), String::new()); entries];
-    for i in 0..entries {
-        v[i] = (random_string(16), random_string(16));
+    for e in v.iter_mut() {
+        *e = (random_string(16), random_string(16));
     }
     v.sort();
 
     let dst = fs::OpenOptions::new()
         .create(true)
+        .truncate(true)
         .write(true)
         .open("/tmp/.sstabletestfile")
         .unwrap();
@@ -58,8 +58,8 @@ fn main() {
     let tr = read_table(&path).expect("Reading the table failed");
 
     println!("=== lookups ===");
-    println!("{} => {:?}", "000", lookup(&tr, "000").unwrap());
-    println!("{} => {:?}", "def", lookup(&tr, "def").unwrap());
-    println!("{} => {:?}", "zzy", lookup(&tr, "zzy").unwrap());
-    println!("{} => {:?}", "zzz", lookup(&tr, "zzz").unwrap());
+    println!("000 => {:?}", lookup(&tr, "000").unwrap());
+    println!("def => {:?}", lookup(&tr, "def").unwrap());
+    println!("zzy => {:?}", lookup(&tr, "zzy").unwrap());
+    println!("zzz => {:?}", lookup(&tr, "zzz").unwrap());
 }
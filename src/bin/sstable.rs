@@ -0,0 +1,511 @@
+//! `sstable` is a command-line tool for inspecting and building tables. It is only built with the
+//! `cli` feature enabled.
+//!
+//! Keys and values are printed (and, as arguments and in TSV input, parsed) with non-printable
+//! bytes escaped: `\t`, `\n`, `\r`, `\\` and `\xNN` for other bytes that aren't printable UTF-8.
+//! The output of `sstable dump` can therefore be used as input for `sstable build`.
+
+use sstable::{
+    BlockFormat, ChecksumType, CompressionType, DuplicateKeyPolicy, Options, ReadOptions,
+    SSIterator, SortingTableBuilder, Status, StatusCode, Table, TableIterator,
+};
+
+use std::fs;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+#[derive(Parser)]
+#[command(name = "sstable", about = "Inspect and build SSTable files")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print all entries as tab-separated key/value pairs, followed by a summary on stderr.
+    Dump {
+        table: PathBuf,
+        /// Print keys and values as hex strings.
+        #[arg(long)]
+        hex: bool,
+    },
+    /// Print the value of a key.
+    Get { table: PathBuf, key: String },
+    /// Print the entries with keys in the range [FROM, TO).
+    Scan {
+        table: PathBuf,
+        #[arg(long)]
+        from: Option<String>,
+        #[arg(long)]
+        to: Option<String>,
+        /// Print keys and values as hex strings.
+        #[arg(long)]
+        hex: bool,
+    },
+    /// Print information on the layout of a table.
+    Stats { table: PathBuf },
+    /// Print the data blocks and meta blocks of a table.
+    Blocks {
+        table: PathBuf,
+        /// Print keys as hex strings.
+        #[arg(long)]
+        hex: bool,
+    },
+    /// Verify the checksums of all blocks and the order of all keys, and the file checksum if the
+    /// table has one.
+    Verify { table: PathBuf },
+    /// Build a table from key/value pairs, which don't need to be sorted.
+    Build {
+        /// Path of the table to write.
+        output: PathBuf,
+        /// Input file; stdin if not given.
+        #[arg(long, short)]
+        input: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = InputFormat::Tsv)]
+        format: InputFormat,
+        #[arg(long, value_enum, default_value_t = Compression::None)]
+        compression: Compression,
+        #[arg(long, default_value_t = Options::default().block_size)]
+        block_size: usize,
+        /// Use the compact block format.
+        #[arg(long)]
+        compact: bool,
+        #[arg(long, value_enum, default_value_t = Checksum::Crc32c)]
+        checksum: Checksum,
+        /// Record a checksum of the whole file.
+        #[arg(long)]
+        file_checksum: bool,
+        /// Which entry to keep if a key occurs more than once.
+        #[arg(long, value_enum, default_value_t = Duplicates::Last)]
+        duplicates: Duplicates,
+        /// Directory for temporary files; the system's temporary directory if not given.
+        #[arg(long)]
+        tmp_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum InputFormat {
+    /// One entry per line: escaped key, tab, escaped value.
+    Tsv,
+    /// One JSON object per line: {"key": "...", "value": "..."}.
+    Jsonl,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Compression {
+    None,
+    Snappy,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Checksum {
+    Crc32c,
+    Xxhash64,
+    Xxh3,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Duplicates {
+    First,
+    Last,
+    Error,
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("sstable: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command) -> Result<ExitCode, Status> {
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    match command {
+        Command::Dump { table, hex } => {
+            let table = Table::new_from_file(Options::default(), &table)?;
+            let mut iter = table.iter_with_options(&scan_options());
+            print_entries(&mut out, &mut iter, None, hex)?;
+        }
+        Command::Get { table, key } => {
+            let table = Table::new_from_file(Options::default(), &table)?;
+            match table.get(&unescape(&key)?)? {
+                Some(val) => writeln!(out, "{}", escape(&val))?,
+                None => {
+                    eprintln!("sstable: key not found");
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+        }
+        Command::Scan {
+            table,
+            from,
+            to,
+            hex,
+        } => {
+            let table = Table::new_from_file(Options::default(), &table)?;
+            let from = from.map(|k| unescape(&k)).transpose()?;
+            let to = to.map(|k| unescape(&k)).transpose()?;
+            match (from, to) {
+                (from, Some(to)) => {
+                    let mut iter = table.iter_range_with_options(
+                        from.as_deref().unwrap_or_default(),
+                        &to,
+                        &scan_options(),
+                    );
+                    print_entries(&mut out, &mut iter, None, hex)?;
+                }
+                (from, None) => {
+                    let mut iter = table.iter_with_options(&scan_options());
+                    print_entries(&mut out, &mut iter, from.as_deref(), hex)?;
+                }
+            }
+        }
+        Command::Stats { table: path } => {
+            let table = Table::new_from_file(Options::default(), &path)?;
+            let stats = table.stats()?;
+            writeln!(out, "file size:          {}", fs::metadata(&path)?.len())?;
+            match stats.rocksdb_format_version {
+                Some(version) => writeln!(out, "format version:     RocksDB {}", version)?,
+                None => writeln!(out, "format version:     {}", stats.format_version)?,
+            }
+            if stats.features.is_empty() {
+                writeln!(out, "features:           none")?;
+            } else {
+                writeln!(out, "features:           {}", stats.features.join(", "))?;
+            }
+            writeln!(out, "checksum type:      {:?}", stats.checksum_type)?;
+            writeln!(
+                out,
+                "data blocks:        {} ({} compressed), {} bytes",
+                stats.data_blocks, stats.compressed_data_blocks, stats.data_size
+            )?;
+            writeln!(
+                out,
+                "index block:        offset {}, {} bytes",
+                stats.index.0, stats.index.1
+            )?;
+            writeln!(
+                out,
+                "metaindex block:    offset {}, {} bytes",
+                stats.metaindex.0, stats.metaindex.1
+            )?;
+            match stats.filter {
+                Some((name, size)) => {
+                    writeln!(out, "filter:             {}, {} bytes", name, size)?
+                }
+                None => writeln!(out, "filter:             none")?,
+            }
+            if let Some(size) = stats.range_filter_size {
+                writeln!(out, "range filter:       {} bytes", size)?;
+            }
+            if let Some(props) = table.properties()? {
+                writeln!(out, "entries:            {}", props.num_entries)?;
+                writeln!(
+                    out,
+                    "raw key/value size: {} / {} bytes",
+                    props.raw_key_size, props.raw_value_size
+                )?;
+            }
+        }
+        Command::Blocks { table, hex } => {
+            let table = Table::new_from_file(Options::default(), &table)?;
+            let format = |b: &[u8]| if hex { to_hex(b) } else { escape(b) };
+            for b in table.blocks()? {
+                writeln!(
+                    out,
+                    "data block at {}: {} bytes ({} uncompressed, {:?}), {} entries, {} restarts, keys {} .. {}",
+                    b.offset,
+                    b.size,
+                    b.uncompressed_size,
+                    b.compression,
+                    b.entries,
+                    b.restarts,
+                    format(&b.first_key),
+                    format(&b.last_key)
+                )?;
+            }
+            for m in table.meta_blocks()? {
+                writeln!(
+                    out,
+                    "meta block at {}: {} bytes, {}",
+                    m.offset,
+                    m.size,
+                    escape(&m.name)
+                )?;
+            }
+        }
+        Command::Verify { table } => {
+            let table = Table::new_from_file(Options::default(), &table)?;
+            let entries = table.verify()?;
+            match table.verify_file_checksum() {
+                Ok(()) => writeln!(out, "OK: {} entries, file checksum matches", entries)?,
+                Err(e) if e.code == StatusCode::NotSupported => {
+                    writeln!(out, "OK: {} entries", entries)?
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Command::Build {
+            output,
+            input,
+            format,
+            compression,
+            block_size,
+            compact,
+            checksum,
+            file_checksum,
+            duplicates,
+            tmp_dir,
+        } => {
+            let opt = Options {
+                block_size,
+                compression_type: match compression {
+                    Compression::None => CompressionType::CompressionNone,
+                    Compression::Snappy => CompressionType::CompressionSnappy,
+                },
+                block_format: if compact {
+                    BlockFormat::Compact
+                } else {
+                    BlockFormat::Standard
+                },
+                checksum_type: match checksum {
+                    Checksum::Crc32c => ChecksumType::Crc32c,
+                    Checksum::Xxhash64 => ChecksumType::XxHash64,
+                    Checksum::Xxh3 => ChecksumType::Xxh3,
+                },
+                file_checksum,
+                ..Options::default()
+            };
+            let policy = match duplicates {
+                Duplicates::First => DuplicateKeyPolicy::KeepFirst,
+                Duplicates::Last => DuplicateKeyPolicy::KeepLast,
+                Duplicates::Error => DuplicateKeyPolicy::Error,
+            };
+            let input: Box<dyn BufRead> = match input {
+                Some(path) => Box::new(io::BufReader::new(fs::File::open(path)?)),
+                None => Box::new(io::stdin().lock()),
+            };
+
+            let dst = BufWriter::new(fs::File::create(&output)?);
+            let mut builder = SortingTableBuilder::new(opt, dst).with_duplicate_key_policy(policy);
+            if let Some(dir) = tmp_dir {
+                builder = builder.with_tmp_dir(&dir);
+            }
+            // Don't leave a partial table behind.
+            let (entries, size) = add_entries(builder, input, format).inspect_err(|_| {
+                let _ = fs::remove_file(&output);
+            })?;
+            writeln!(out, "wrote {} entries, {} bytes", entries, size)?;
+        }
+    }
+    out.flush()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Adds the entries read from `input` to `builder` and finishes the table. Returns the number of
+/// entries read and the size of the table.
+fn add_entries<W: Write>(
+    mut builder: SortingTableBuilder<W>,
+    input: Box<dyn BufRead>,
+    format: InputFormat,
+) -> Result<(usize, usize), Status> {
+    let mut entries = 0;
+    for (n, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let (key, val) = match format {
+            InputFormat::Tsv => parse_tsv(&line),
+            InputFormat::Jsonl => parse_jsonl(&line),
+        }
+        .map_err(|e| Status {
+            code: e.code,
+            err: format!("line {}: {}", n + 1, e.err),
+        })?;
+        builder.add(&key, &val)?;
+        entries += 1;
+    }
+    Ok((entries, builder.finish()?))
+}
+
+/// Full scans shouldn't fill the block cache.
+fn scan_options() -> ReadOptions {
+    ReadOptions {
+        fill_cache: false,
+        ..ReadOptions::default()
+    }
+}
+
+/// Prints the entries of `iter`, starting at `from` if given, and a summary on stderr.
+fn print_entries<W: Write>(
+    out: &mut W,
+    iter: &mut TableIterator,
+    from: Option<&[u8]>,
+    hex: bool,
+) -> Result<(), Status> {
+    let format = |b: &[u8]| if hex { to_hex(b) } else { escape(b) };
+    let (mut key, mut val) = (vec![], vec![]);
+    let mut entries = 0;
+    let mut first = None;
+    let mut valid = match from {
+        Some(from) => {
+            iter.seek(from);
+            iter.valid()
+        }
+        None => iter.advance(),
+    };
+    while valid {
+        iter.current(&mut key, &mut val);
+        writeln!(out, "{}\t{}", format(&key), format(&val))?;
+        if first.is_none() {
+            first = Some(key.clone());
+        }
+        entries += 1;
+        valid = iter.advance();
+    }
+    out.flush()?;
+    iter.status()?;
+    match first {
+        Some(first) => eprintln!(
+            "{} entries, keys {} .. {}",
+            entries,
+            format(&first),
+            format(&key)
+        ),
+        None => eprintln!("0 entries"),
+    }
+    Ok(())
+}
+
+fn to_hex(b: &[u8]) -> String {
+    b.iter().map(|c| format!("{:02x}", c)).collect()
+}
+
+fn escape(b: &[u8]) -> String {
+    let mut s = String::with_capacity(b.len());
+    for chunk in b.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\t' => s.push_str("\\t"),
+                '\n' => s.push_str("\\n"),
+                '\r' => s.push_str("\\r"),
+                '\\' => s.push_str("\\\\"),
+                c if c.is_control() => {
+                    let mut buf = [0; 4];
+                    for byte in c.encode_utf8(&mut buf).bytes() {
+                        s.push_str(&format!("\\x{:02x}", byte));
+                    }
+                }
+                c => s.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            s.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    s
+}
+
+fn unescape(s: &str) -> Result<Vec<u8>, Status> {
+    let bad_escape = || Status::new(StatusCode::InvalidArgument, "bad escape sequence");
+    let mut b = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(c) = bytes.next() {
+        if c != b'\\' {
+            b.push(c);
+            continue;
+        }
+        match bytes.next() {
+            Some(b't') => b.push(b'\t'),
+            Some(b'n') => b.push(b'\n'),
+            Some(b'r') => b.push(b'\r'),
+            Some(b'\\') => b.push(b'\\'),
+            Some(b'x') => {
+                let hex = [
+                    bytes.next().ok_or_else(bad_escape)?,
+                    bytes.next().ok_or_else(bad_escape)?,
+                ];
+                let hex = std::str::from_utf8(&hex).map_err(|_| bad_escape())?;
+                b.push(u8::from_str_radix(hex, 16).map_err(|_| bad_escape())?);
+            }
+            _ => return Err(bad_escape()),
+        }
+    }
+    Ok(b)
+}
+
+fn parse_tsv(line: &str) -> Result<(Vec<u8>, Vec<u8>), Status> {
+    match line.split_once('\t') {
+        Some((key, val)) => Ok((unescape(key)?, unescape(val)?)),
+        None => Err(Status::new(
+            StatusCode::InvalidArgument,
+            "expected key and value separated by a tab",
+        )),
+    }
+}
+
+fn parse_jsonl(line: &str) -> Result<(Vec<u8>, Vec<u8>), Status> {
+    let bad_entry = || {
+        Status::new(
+            StatusCode::InvalidArgument,
+            "expected an object with string fields \"key\" and \"value\"",
+        )
+    };
+    let entry: serde_json::Value = serde_json::from_str(line)
+        .map_err(|e| Status::new(StatusCode::InvalidArgument, &e.to_string()))?;
+    let field = |name| {
+        entry
+            .get(name)
+            .and_then(|v| v.as_str())
+            .map(|s| s.as_bytes().to_vec())
+            .ok_or_else(bad_entry)
+    };
+    Ok((field("key")?, field("value")?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        let cases: &[(&[u8], &str)] = &[
+            (b"plain", "plain"),
+            (b"a\tb\nc\rd\\e", "a\\tb\\nc\\rd\\\\e"),
+            (b"\x00\x7f\xff", "\\x00\\x7f\\xff"),
+            ("grüße".as_bytes(), "grüße"),
+        ];
+        for &(raw, escaped) in cases {
+            assert_eq!(escape(raw), escaped);
+            assert_eq!(unescape(escaped).unwrap(), raw);
+        }
+        assert!(unescape("\\q").is_err());
+        assert!(unescape("\\x4").is_err());
+        assert!(unescape("\\xzz").is_err());
+    }
+
+    #[test]
+    fn test_parse_input() {
+        assert_eq!(
+            parse_tsv("k\\x00\tv\\tw").unwrap(),
+            (b"k\x00".to_vec(), b"v\tw".to_vec())
+        );
+        assert!(parse_tsv("no tab").is_err());
+        assert_eq!(
+            parse_jsonl(r#"{"key": "k", "value": "v\n"}"#).unwrap(),
+            (b"k".to_vec(), b"v\n".to_vec())
+        );
+        assert!(parse_jsonl(r#"{"key": "k"}"#).is_err());
+        assert!(parse_jsonl("not json").is_err());
+    }
+}
@@ -0,0 +1,153 @@
+//! Value separation: large values of a table can be stored in a separate blob file, and the table
+//! only contains references to them.
+//!
+//! A blob file is a sequence of records, each consisting of a VALUE followed by a fixed u32
+//! masked crc32c checksum of the VALUE.
+//!
+//! In a table with a blob file (FEATURE_BLOB_VALUES), every value starts with a tag byte: Values
+//! tagged VALUE_INLINE are stored in the table, following the tag. Values tagged VALUE_BLOB are
+//! stored in the blob file; the tag is followed by the offset and the length of the VALUE in the
+//! blob file, as varints.
+
+use crate::error::{err, Result, StatusCode};
+use crate::table_builder::CRC32C;
+use crate::types::{mask_crc, unmask_crc, RandomAccess};
+
+use std::io::Write;
+
+use integer_encoding::{FixedInt, FixedIntWriter, VarInt, VarIntWriter};
+
+const VALUE_INLINE: u8 = 0;
+const VALUE_BLOB: u8 = 1;
+
+const BLOB_CKSUM_LEN: usize = 4;
+
+/// BlobWriter appends values to a blob file.
+pub struct BlobWriter<Dst: Write> {
+    dst: Dst,
+    offset: usize,
+}
+
+impl<Dst: Write> BlobWriter<Dst> {
+    pub fn new(dst: Dst) -> BlobWriter<Dst> {
+        BlobWriter { dst, offset: 0 }
+    }
+
+    /// Writes `val` to the blob file and appends a reference to it to `tagged`.
+    pub fn add(&mut self, val: &[u8], tagged: &mut Vec<u8>) -> Result<()> {
+        self.dst.write_all(val)?;
+        self.dst.write_fixedint(mask_crc(CRC32C.checksum(val)))?;
+
+        tagged.push(VALUE_BLOB);
+        tagged.write_varint(self.offset)?;
+        tagged.write_varint(val.len())?;
+
+        self.offset += val.len() + BLOB_CKSUM_LEN;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.dst.flush()?)
+    }
+}
+
+/// Appends `val` as inline value to `tagged`.
+pub fn tag_inline(val: &[u8], tagged: &mut Vec<u8>) {
+    tagged.push(VALUE_INLINE);
+    tagged.extend_from_slice(val);
+}
+
+/// Replaces the tagged value `val` with the actual value, which is read from `blob_file` if
+/// necessary.
+pub fn resolve(
+    val: &mut Vec<u8>,
+    blob_file: Option<&dyn RandomAccess>,
+    verify_checksum: bool,
+) -> Result<()> {
+    match val.first() {
+        Some(&VALUE_INLINE) => {
+            val.remove(0);
+            Ok(())
+        }
+        Some(&VALUE_BLOB) => {
+            let (offset, offlen) = match usize::decode_var(&val[1..]) {
+                Some(v) => v,
+                None => return err(StatusCode::Corruption, "bad blob reference"),
+            };
+            let (len, _) = match usize::decode_var(&val[1 + offlen..]) {
+                Some(v) => v,
+                None => return err(StatusCode::Corruption, "bad blob reference"),
+            };
+            let blob_file = match blob_file {
+                Some(f) => f,
+                None => return err(StatusCode::InvalidArgument, "table has no blob file"),
+            };
+
+            let mut buf = vec![0; len + BLOB_CKSUM_LEN];
+            if blob_file.read_at(offset, &mut buf)? < buf.len() {
+                return err(
+                    StatusCode::Corruption,
+                    &format!("blob at {} is past the end of the blob file", offset),
+                );
+            }
+            let cksum = buf.split_off(len);
+            if verify_checksum && CRC32C.checksum(&buf) != unmask_crc(u32::decode_fixed(&cksum)) {
+                return err(
+                    StatusCode::Corruption,
+                    &format!("checksum verification failed for blob at {}", offset),
+                );
+            }
+            *val = buf;
+            Ok(())
+        }
+        _ => err(StatusCode::Corruption, "bad value tag"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blob_roundtrip() {
+        let mut blob_file = vec![];
+        let mut refs = vec![];
+        {
+            let mut w = BlobWriter::new(&mut blob_file);
+            for v in [&b"first value"[..], b"", b"third"] {
+                let mut tagged = vec![];
+                w.add(v, &mut tagged).unwrap();
+                refs.push(tagged);
+            }
+        }
+        let mut inline = vec![];
+        tag_inline(b"inline", &mut inline);
+
+        for (tagged, v) in refs.iter().zip([&b"first value"[..], b"", b"third"]) {
+            let mut val = tagged.clone();
+            resolve(&mut val, Some(&blob_file), true).unwrap();
+            assert_eq!(val, v);
+        }
+        let mut val = inline.clone();
+        resolve(&mut val, None, true).unwrap();
+        assert_eq!(val, b"inline");
+
+        let mut val = refs[0].clone();
+        assert_eq!(
+            resolve(&mut val, None, true).unwrap_err().code,
+            StatusCode::InvalidArgument
+        );
+        assert!(resolve(&mut vec![], Some(&blob_file), true).is_err());
+
+        // Corrupt the first blob.
+        blob_file[0] ^= 1;
+        let mut val = refs[0].clone();
+        assert_eq!(
+            resolve(&mut val, Some(&blob_file), true).unwrap_err().code,
+            StatusCode::Corruption
+        );
+        let mut val = refs[0].clone();
+        resolve(&mut val, Some(&blob_file), false).unwrap();
+        assert_eq!(val, b"girst value");
+    }
+}
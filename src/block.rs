@@ -1,8 +1,9 @@
 use std::cmp::Ordering;
 
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
-use crate::options::Options;
+use crate::filter::hash64;
+use crate::options::{BlockFormat, Options};
 use crate::types::SSIterator;
 
 use integer_encoding::FixedInt;
@@ -10,6 +11,42 @@ use integer_encoding::VarInt;
 
 pub type BlockContents = Vec<u8>;
 
+/// Set in the N_RESTARTS field of blocks with a hash index.
+pub const HASH_INDEX_FLAG: u32 = 1 << 31;
+/// A hash index can only point to restart points below this limit.
+pub const HASH_INDEX_MAX_RESTARTS: usize = 254;
+/// Bucket values for buckets without keys and buckets with keys from different restart intervals.
+pub const HASH_BUCKET_EMPTY: u8 = 255;
+pub const HASH_BUCKET_COLLISION: u8 = 254;
+
+/// Returns the hash index bucket of a key with hash `h` (see `filter::hash64`) in a hash index
+/// with `num_buckets` buckets.
+pub fn hash_bucket(h: u64, num_buckets: usize) -> usize {
+    (h % num_buckets as u64) as usize
+}
+
+/// Appends `v` as a reversed varint, which can be decoded from its end by `decode_var_rev()`.
+pub fn write_var_rev(dst: &mut Vec<u8>, v: usize) {
+    let mut buf = [0; 10];
+    let len = v.encode_var(&mut buf);
+    dst.extend(buf[..len].iter().rev());
+}
+
+/// Decodes the reversed varint at the end of `src`; returns the value and its length.
+fn decode_var_rev(src: &[u8]) -> (usize, usize) {
+    let mut buf = [0; 10];
+    let mut len = 0;
+    for &b in src.iter().rev().take(buf.len()) {
+        buf[len] = b;
+        len += 1;
+        if b & 0x80 == 0 {
+            break;
+        }
+    }
+    let (v, _) = usize::decode_var(&buf[..len]).unwrap_or((0, 0));
+    (v, len)
+}
+
 /// A Block is an immutable ordered set of key/value entries.
 ///
 /// The structure internally looks like follows:
@@ -30,10 +67,23 @@ pub type BlockContents = Vec<u8>;
 /// A RESTART is a fixed u32 pointing to the beginning of an ENTRY.
 ///
 /// N_RESTARTS contains the number of restarts.
+///
+/// Data blocks may have a HASH INDEX between the RESTARTS and N_RESTARTS; the HASH_INDEX_FLAG bit
+/// of N_RESTARTS is set then. The hash index consists of BUCKETS, followed by a fixed u16
+/// N_BUCKETS. A BUCKET is a byte containing the restart point of the restart interval of all keys
+/// hashing to it, or HASH_BUCKET_EMPTY or HASH_BUCKET_COLLISION.
+///
+/// Blocks of tables with `BlockFormat::Compact` store the RESTARTS as varint-encoded differences to
+/// the previous restart point (the first one to 0). Instead of N_RESTARTS, they end with a
+/// trailer of two varints, encoded in reverse byte order so that they can be read from the end
+/// of the block: the byte length of the RESTARTS, and N_RESTARTS multiplied by 2, plus 1 if the
+/// block has a HASH INDEX. The latter comes last.
 #[derive(Clone)]
 pub struct Block {
     block: Arc<BlockContents>,
     opt: Options,
+    // The decoded RESTARTS of a compact block.
+    restarts: Arc<OnceLock<Vec<u32>>>,
 }
 
 impl Block {
@@ -42,12 +92,33 @@ impl Block {
     /// refcounted block contents as this block, meaning that if the iterator isn't released,
     /// the memory occupied by the block isn't, either)
     pub fn iter(&self) -> BlockIter {
-        let restarts = u32::decode_fixed(&self.block[self.block.len() - 4..]);
-        let restart_offset = self.block.len() - 4 - 4 * restarts as usize;
+        let (mut num_restarts, restart_offset, hash_index_off, num_buckets) =
+            self.decode_trailer().unwrap_or((0, 0, 0, 0));
+
+        if self.opt.block_format == BlockFormat::Compact {
+            let restarts = self.restarts.get_or_init(|| {
+                let mut restarts = Vec::with_capacity(num_restarts);
+                let mut restart = 0;
+                let mut off = restart_offset;
+                while off < hash_index_off && restarts.len() < num_restarts {
+                    let (delta, delta_len) = u32::decode_var(&self.block[off..]).unwrap_or((0, 1));
+                    restart += delta;
+                    restarts.push(restart);
+                    off += delta_len;
+                }
+                restarts
+            });
+            num_restarts = num_restarts.min(restarts.len());
+        }
 
         BlockIter {
             block: self.block.clone(),
             opt: self.opt.clone(),
+            restarts: self.restarts.clone(),
+
+            num_restarts,
+            hash_index_off,
+            num_buckets,
 
             offset: 0,
             restarts_off: restart_offset,
@@ -59,15 +130,58 @@ impl Block {
         }
     }
 
+    /// Decodes the end of the block. Returns N_RESTARTS, the offsets of the RESTARTS and of the
+    /// HASH INDEX, and the number of hash buckets (0 without a hash index), or None if the block is
+    /// too short for what its trailer describes.
+    fn decode_trailer(&self) -> Option<(usize, usize, usize, usize)> {
+        let len = self.block.len();
+        let (num_restarts, has_hash_index, restarts_len, trailer_off) = match self.opt.block_format
+        {
+            BlockFormat::Standard => {
+                let trailer_off = len.checked_sub(4)?;
+                let footer = u32::decode_fixed(&self.block[trailer_off..]);
+                let num_restarts = (footer & !HASH_INDEX_FLAG) as usize;
+                (
+                    num_restarts,
+                    footer & HASH_INDEX_FLAG != 0,
+                    num_restarts.checked_mul(4)?,
+                    trailer_off,
+                )
+            }
+            BlockFormat::Compact => {
+                let (header, header_len) = decode_var_rev(&self.block);
+                let restarts_len_end = len.checked_sub(header_len)?;
+                let (restarts_len, restarts_len_len) =
+                    decode_var_rev(&self.block[..restarts_len_end]);
+                (
+                    header >> 1,
+                    header & 1 != 0,
+                    restarts_len,
+                    restarts_len_end.checked_sub(restarts_len_len)?,
+                )
+            }
+        };
+        let (num_buckets, hash_index_off) = if has_hash_index {
+            let num_buckets_off = trailer_off.checked_sub(2)?;
+            let num_buckets = u16::decode_fixed(&self.block[num_buckets_off..trailer_off]) as usize;
+            (num_buckets, num_buckets_off.checked_sub(num_buckets)?)
+        } else {
+            (0, trailer_off)
+        };
+        let restart_offset = hash_index_off.checked_sub(restarts_len)?;
+        Some((num_restarts, restart_offset, hash_index_off, num_buckets))
+    }
+
     pub fn contents(&self) -> Arc<BlockContents> {
         self.block.clone()
     }
 
     pub fn new(opt: Options, contents: BlockContents) -> Block {
-        assert!(contents.len() > 4);
+        assert!(contents.len() > 4 || opt.block_format == BlockFormat::Compact);
         Block {
             block: Arc::new(contents),
-            opt: opt,
+            opt,
+            restarts: Arc::new(OnceLock::new()),
         }
     }
 }
@@ -78,6 +192,12 @@ pub struct BlockIter {
     /// The underlying block contents.
     block: Arc<BlockContents>,
     opt: Options,
+    /// The decoded restart points of a compact block.
+    restarts: Arc<OnceLock<Vec<u32>>>,
+    num_restarts: usize,
+    /// offset and size of the hash index; num_buckets is 0 if there is none.
+    hash_index_off: usize,
+    num_buckets: usize,
     /// offset of restarts area within the block.
     restarts_off: usize,
 
@@ -96,8 +216,8 @@ pub struct BlockIter {
 
 impl BlockIter {
     /// Return the number of restarts in this block.
-    fn number_restarts(&self) -> usize {
-        u32::decode_fixed(&self.block[self.block.len() - 4..]) as usize
+    pub(crate) fn number_restarts(&self) -> usize {
+        self.num_restarts
     }
 
     /// Seek to restart point `ix`. After the seek, current() will return the entry at that restart
@@ -118,6 +238,9 @@ impl BlockIter {
 
     /// Return the offset that restart `ix` points to.
     fn get_restart_point(&self, ix: usize) -> usize {
+        if let Some(restarts) = self.restarts.get() {
+            return restarts[ix] as usize;
+        }
         let restart = self.restarts_off + 4 * ix;
         u32::decode_fixed(&self.block[restart..restart + 4]) as usize
     }
@@ -152,7 +275,7 @@ impl BlockIter {
     /// the part of the key that is different from the previous key).
     ///
     /// `off` is the offset of the key string within the whole block (self.current_entry_offset
-    /// + entry header length); `shared` and `non_shared` are the lengths of the shared
+    /// plus entry header length); `shared` and `non_shared` are the lengths of the shared
     /// respectively non-shared parts of the key.
     /// Only self.key is mutated.
     fn assemble_key(&mut self, off: usize, shared: usize, non_shared: usize) {
@@ -161,6 +284,36 @@ impl BlockIter {
             .extend_from_slice(&self.block[off..off + non_shared]);
     }
 
+    /// Positions the iterator like `seek(key)` for a point lookup of `key`, using the hash index
+    /// if the block has one. Returns false if the block doesn't contain `key`; the iterator is
+    /// reset then. Otherwise, the caller has to check whether the current key is `key`.
+    pub fn seek_for_get(&mut self, key: &[u8]) -> bool {
+        if self.num_buckets > 0 {
+            let bucket = hash_bucket(hash64(key), self.num_buckets);
+            match self.block[self.hash_index_off + bucket] {
+                HASH_BUCKET_EMPTY => {
+                    self.reset();
+                    return false;
+                }
+                HASH_BUCKET_COLLISION => {}
+                ix if (ix as usize) < self.number_restarts() => {
+                    // Linear search from the restart point of the key's interval.
+                    self.seek_to_restart_point(ix as usize);
+                    while self.opt.cmp.cmp(&self.key, key) == Ordering::Less {
+                        if !self.advance() {
+                            return false;
+                        }
+                    }
+                    return true;
+                }
+                // Corrupted hash index; use the binary search.
+                _ => {}
+            }
+        }
+        self.seek(key);
+        self.valid()
+    }
+
     pub fn seek_to_last(&mut self) {
         if self.number_restarts() > 0 {
             let num_restarts = self.number_restarts();
@@ -249,17 +402,16 @@ impl SSIterator for BlockIter {
 
     fn seek(&mut self, to: &[u8]) {
         self.reset();
+        if self.number_restarts() == 0 {
+            return;
+        }
 
         let mut left = 0;
-        let mut right = if self.number_restarts() == 0 {
-            0
-        } else {
-            self.number_restarts() - 1
-        };
+        let mut right = self.number_restarts() - 1;
 
         // Do a binary search over the restart points.
         while left < right {
-            let middle = (left + right + 1) / 2;
+            let middle = (left + right).div_ceil(2);
             self.seek_to_restart_point(middle);
 
             let c = self.opt.cmp.cmp(&self.key, to);
@@ -356,9 +508,7 @@ mod tests {
 
         let block = Block::new(Options::default(), blockc);
 
-        for _ in SSIteratorIter::wrap(&mut block.iter()) {
-            panic!("expected 0 iterations");
-        }
+        assert_eq!(SSIteratorIter::wrap(&mut block.iter()).count(), 0);
     }
 
     #[test]
@@ -417,7 +567,7 @@ mod tests {
 
         // Verify that prev() from the last entry goes to the prev-to-last entry
         // (essentially, that next() returning None doesn't advance anything)
-        while let Some(_) = block.next() {}
+        while block.next().is_some() {}
 
         block.prev();
         assert!(block.valid());
@@ -446,7 +596,7 @@ mod tests {
 
         let mut block = Block::new(o.clone(), block_contents).iter();
 
-        block.seek(&"prefix_key2".as_bytes());
+        block.seek("prefix_key2".as_bytes());
         assert!(block.valid());
         assert_eq!(
             current_key_val(&block),
@@ -456,7 +606,7 @@ mod tests {
             ))
         );
 
-        block.seek(&"prefix_key0".as_bytes());
+        block.seek("prefix_key0".as_bytes());
         assert!(block.valid());
         assert_eq!(
             current_key_val(&block),
@@ -466,14 +616,14 @@ mod tests {
             ))
         );
 
-        block.seek(&"key1".as_bytes());
+        block.seek("key1".as_bytes());
         assert!(block.valid());
         assert_eq!(
             current_key_val(&block),
             Some(("key1".as_bytes().to_vec(), "value1".as_bytes().to_vec()))
         );
 
-        block.seek(&"prefix_key3".as_bytes());
+        block.seek("prefix_key3".as_bytes());
         assert!(block.valid());
         assert_eq!(
             current_key_val(&block),
@@ -483,17 +633,132 @@ mod tests {
             ))
         );
 
-        block.seek(&"prefix_key8".as_bytes());
+        block.seek("prefix_key8".as_bytes());
         assert!(!block.valid());
         assert_eq!(current_key_val(&block), None);
     }
 
+    #[test]
+    fn test_block_hash_index() {
+        let mut o = Options::default();
+        o.block_restart_interval = 2;
+
+        let data = get_data();
+        let mut builder = BlockBuilder::new(o.clone()).with_hash_index(0.75);
+        for &(k, v) in data.iter() {
+            builder.add(k, v);
+        }
+        let estimate = builder.size_estimate();
+        let block_contents = builder.finish();
+        assert_eq!(estimate, block_contents.len());
+        assert_ne!(
+            u32::decode_fixed(&block_contents[block_contents.len() - 4..]) & HASH_INDEX_FLAG,
+            0
+        );
+
+        // The block is still readable without the hash index.
+        let mut block = Block::new(o.clone(), block_contents).iter();
+        assert_eq!(
+            SSIteratorIter::wrap(&mut block)
+                .map(|(k, _)| k)
+                .collect::<Vec<_>>(),
+            data.iter().map(|(k, _)| k.to_vec()).collect::<Vec<_>>()
+        );
+        block.seek(b"prefix_key0");
+        assert_eq!(block.current_key(), Some(&b"prefix_key1"[..]));
+        block.seek_to_last();
+        assert_eq!(block.current_key(), Some(&b"prefix_key3"[..]));
+
+        for &(k, v) in data.iter() {
+            assert!(block.seek_for_get(k));
+            assert_eq!(current_key_val(&block), Some((k.to_vec(), v.to_vec())));
+        }
+        for k in [
+            &b"key0"[..],
+            b"key2",
+            b"prefix_key0",
+            b"prefix_key4",
+            b"zzz",
+        ] {
+            if block.seek_for_get(k) {
+                assert_ne!(block.current_key(), Some(k));
+            } else {
+                assert!(!block.valid());
+            }
+        }
+    }
+
+    #[test]
+    fn test_block_compact_format() {
+        let data = get_data();
+        let build = |o: &Options, hash_index: bool| {
+            let mut builder = BlockBuilder::new(o.clone());
+            if hash_index {
+                builder = builder.with_hash_index(0.75);
+            }
+            for &(k, v) in data.iter() {
+                builder.add(k, v);
+            }
+            let estimate = builder.size_estimate();
+            let contents = builder.finish();
+            assert_eq!(estimate, contents.len());
+            contents
+        };
+
+        let mut o = Options::default();
+        o.block_restart_interval = 2;
+        let standard_len = build(&o, false).len();
+        o.block_format = BlockFormat::Compact;
+
+        for &hash_index in &[false, true] {
+            let contents = build(&o, hash_index);
+            if !hash_index {
+                assert!(contents.len() < standard_len);
+            }
+            let mut iter = Block::new(o.clone(), contents).iter();
+            assert_eq!(
+                SSIteratorIter::wrap(&mut iter).collect::<Vec<_>>(),
+                data.iter()
+                    .map(|(k, v)| (k.to_vec(), v.to_vec()))
+                    .collect::<Vec<_>>()
+            );
+            iter.seek(b"prefix_key0");
+            assert_eq!(iter.current_key(), Some(&b"prefix_key1"[..]));
+            assert!(iter.prev());
+            assert_eq!(iter.current_key(), Some(&b"medium length key 1"[..]));
+            iter.seek_to_last();
+            assert_eq!(iter.current_key(), Some(&b"prefix_key3"[..]));
+            for &(k, v) in data.iter() {
+                assert!(iter.seek_for_get(k));
+                assert_eq!(current_key_val(&iter), Some((k.to_vec(), v.to_vec())));
+            }
+        }
+
+        // An empty compact block.
+        let block = Block::new(o.clone(), BlockBuilder::new(o.clone()).finish());
+        assert!(!block.iter().advance());
+
+        // Truncated compact blocks: what is left of the trailer points before the block start.
+        for &hash_index in &[false, true] {
+            let contents = build(&o, hash_index);
+            let trailer_len = if hash_index { 4 } else { 2 };
+            for n in 1..=trailer_len {
+                let block = Block::new(o.clone(), contents[contents.len() - n..].to_vec());
+                let mut iter = block.iter();
+                assert!(!iter.advance());
+                iter.seek(b"prefix_key1");
+                assert!(!iter.valid());
+                assert!(!iter.seek_for_get(b"prefix_key1"));
+            }
+        }
+    }
+
     #[test]
     fn test_block_seek_to_last() {
         let mut o = Options::default();
 
         // Test with different number of restarts
-        for block_restart_interval in vec![2, 6, 10] {
+        for block_restart_interval in [2, 6, 10] {
             o.block_restart_interval = block_restart_interval;
 
             let data = get_data();
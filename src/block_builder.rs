@@ -1,9 +1,13 @@
 use std::cmp::Ordering;
 
-use crate::block::BlockContents;
-use crate::options::Options;
+use crate::block::{
+    hash_bucket, write_var_rev, BlockContents, HASH_BUCKET_COLLISION, HASH_BUCKET_EMPTY,
+    HASH_INDEX_FLAG, HASH_INDEX_MAX_RESTARTS,
+};
+use crate::filter::hash64;
+use crate::options::{BlockFormat, Options};
 
-use integer_encoding::{FixedIntWriter, VarIntWriter};
+use integer_encoding::{FixedIntWriter, VarInt, VarIntWriter};
 
 /// BlockBuilder contains functionality for building a block consisting of consecutive key-value
 /// entries.
@@ -11,10 +15,17 @@ pub struct BlockBuilder {
     opt: Options,
     buffer: Vec<u8>,
     restarts: Vec<u32>,
+    // The encoded length of `restarts`.
+    restarts_len: usize,
 
     last_key: Vec<u8>,
     restart_counter: usize,
     counter: usize,
+
+    // If the block gets a hash index: the ratio of keys to buckets, and the hashes of the keys
+    // added so far together with their restart point.
+    hash_ratio: Option<f64>,
+    hash_entries: Vec<(u64, usize)>,
 }
 
 impl BlockBuilder {
@@ -24,11 +35,34 @@ impl BlockBuilder {
 
         BlockBuilder {
             buffer: Vec::with_capacity(o.block_size),
+            restarts_len: match o.block_format {
+                BlockFormat::Standard => 4,
+                BlockFormat::Compact => 1,
+            },
             opt: o,
-            restarts: restarts,
+            restarts,
             last_key: Vec::new(),
             restart_counter: 0,
             counter: 0,
+            hash_ratio: None,
+            hash_entries: vec![],
+        }
+    }
+
+    /// Configures the builder to append a hash index with about `util_ratio` keys per bucket
+    /// (see `block::Block`). Blocks with too many restart points don't get a hash index.
+    pub fn with_hash_index(mut self, util_ratio: f64) -> BlockBuilder {
+        assert!(util_ratio > 0.);
+        self.hash_ratio = Some(util_ratio);
+        self
+    }
+
+    fn num_hash_buckets(&self) -> usize {
+        match self.hash_ratio {
+            Some(ratio) if self.restarts.len() <= HASH_INDEX_MAX_RESTARTS => {
+                ((self.hash_entries.len() as f64 / ratio) as usize + 1).min(u16::MAX as usize)
+            }
+            _ => 0,
         }
     }
 
@@ -36,12 +70,22 @@ impl BlockBuilder {
         self.counter
     }
 
-    pub fn last_key<'a>(&'a self) -> &'a [u8] {
+    pub fn last_key(&self) -> &[u8] {
         &self.last_key
     }
 
     pub fn size_estimate(&self) -> usize {
-        self.buffer.len() + 4 * self.restarts.len() + 4
+        let hash_index = match self.num_hash_buckets() {
+            0 => 0,
+            n => n + 2,
+        };
+        let trailer = match self.opt.block_format {
+            BlockFormat::Standard => 4,
+            BlockFormat::Compact => {
+                self.restarts_len.required_space() + (2 * self.restarts.len()).required_space()
+            }
+        };
+        self.buffer.len() + self.restarts_len + hash_index + trailer
     }
 
     pub fn add(&mut self, key: &[u8], val: &[u8]) {
@@ -64,8 +108,13 @@ impl BlockBuilder {
                 shared += 1;
             }
         } else {
-            self.restarts.push(self.buffer.len() as u32);
-            self.last_key.resize(0, 0);
+            let restart = self.buffer.len() as u32;
+            self.restarts_len += match self.opt.block_format {
+                BlockFormat::Standard => 4,
+                BlockFormat::Compact => (restart - self.restarts.last().unwrap()).required_space(),
+            };
+            self.restarts.push(restart);
+            self.last_key.clear();
             self.restart_counter = 0;
         }
 
@@ -87,24 +136,62 @@ impl BlockBuilder {
         self.last_key.resize(shared, 0);
         self.last_key.extend_from_slice(&key[shared..]);
 
+        if self.hash_ratio.is_some() {
+            self.hash_entries
+                .push((hash64(key), self.restarts.len() - 1));
+        }
+
         self.restart_counter += 1;
         self.counter += 1;
     }
 
     pub fn finish(mut self) -> BlockContents {
-        self.buffer.reserve(self.restarts.len() * 4 + 4);
+        self.buffer.reserve(self.restarts_len + 4);
 
         // 1. Append RESTARTS
+        let mut prev = 0;
         for r in self.restarts.iter() {
+            match self.opt.block_format {
+                BlockFormat::Standard => self.buffer.write_fixedint(*r),
+                BlockFormat::Compact => self.buffer.write_varint(*r - prev),
+            }
+            .expect("write to buffer failed");
+            prev = *r;
+        }
+
+        // 2. Append HASH INDEX
+        let mut n_restarts = self.restarts.len() as u32;
+        let num_buckets = self.num_hash_buckets();
+        if num_buckets > 0 {
+            let mut buckets = vec![HASH_BUCKET_EMPTY; num_buckets];
+            for &(h, restart) in self.hash_entries.iter() {
+                let bucket = &mut buckets[hash_bucket(h, num_buckets)];
+                if *bucket == HASH_BUCKET_EMPTY {
+                    *bucket = restart as u8;
+                } else if *bucket as usize != restart {
+                    *bucket = HASH_BUCKET_COLLISION;
+                }
+            }
+            self.buffer.extend_from_slice(&buckets);
             self.buffer
-                .write_fixedint(*r as u32)
+                .write_fixedint(num_buckets as u16)
                 .expect("write to buffer failed");
+            n_restarts |= HASH_INDEX_FLAG;
         }
 
-        // 2. Append N_RESTARTS
-        self.buffer
-            .write_fixedint(self.restarts.len() as u32)
-            .expect("write to buffer failed");
+        // 3. Append N_RESTARTS, or the trailer of a compact block.
+        match self.opt.block_format {
+            BlockFormat::Standard => {
+                self.buffer
+                    .write_fixedint(n_restarts)
+                    .expect("write to buffer failed");
+            }
+            BlockFormat::Compact => {
+                let header = 2 * self.restarts.len() + usize::from(num_buckets > 0);
+                write_var_rev(&mut self.buffer, self.restarts_len);
+                write_var_rev(&mut self.buffer, header);
+            }
+        }
 
         // done
         self.buffer
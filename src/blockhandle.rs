@@ -27,10 +27,7 @@ impl BlockHandle {
     }
 
     pub fn new(offset: usize, size: usize) -> BlockHandle {
-        BlockHandle {
-            offset: offset,
-            size: size,
-        }
+        BlockHandle { offset, size }
     }
 
     pub fn offset(&self) -> usize {
@@ -57,7 +54,7 @@ mod tests {
     #[test]
     fn test_blockhandle() {
         let bh = BlockHandle::new(890, 777);
-        let mut dst = [0 as u8; 128];
+        let mut dst = [0_u8; 128];
         let enc_sz = bh.encode_to(&mut dst[..]);
 
         let (bh2, dec_sz) = BlockHandle::decode(&dst);
@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use std::mem::{replace, swap};
+use std::mem::swap;
 
 // No clone, no copy! That asserts that an LRUHandle exists only once.
 type LRUHandle<T> = *mut LRUNode<T>;
@@ -43,7 +43,7 @@ impl<T> LRUList<T> {
             // Set up the node after the new one
             self.head.next.as_mut().unwrap().prev = Some(newp);
             // Replace head.next with None and set the new node's next to that
-            new.next = replace(&mut self.head.next, None);
+            new.next = self.head.next.take();
             self.head.next = Some(new);
 
             newp
@@ -67,17 +67,12 @@ impl<T> LRUList<T> {
 
     fn remove_last(&mut self) -> Option<T> {
         if self.head.prev.is_some() {
-            let mut lasto = unsafe {
-                replace(
-                    &mut (*((*self.head.prev.unwrap()).prev.unwrap())).next,
-                    None,
-                )
-            };
+            let mut lasto = unsafe { (*((*self.head.prev.unwrap()).prev.unwrap())).next.take() };
 
             if let Some(ref mut last) = lasto {
                 self.head.prev = last.prev;
                 self.count -= 1;
-                return replace(&mut (*last).data, None);
+                last.data.take()
             } else {
                 None
             }
@@ -90,7 +85,7 @@ impl<T> LRUList<T> {
         unsafe {
             // If has next
             if let Some(ref mut nextp) = (*node_handle).next {
-                swap(&mut (**nextp).prev, &mut (*node_handle).prev);
+                swap(&mut nextp.prev, &mut (*node_handle).prev);
             }
             // If has prev
             if let Some(ref mut prevp) = (*node_handle).prev {
@@ -102,7 +97,7 @@ impl<T> LRUList<T> {
             self.count -= 1;
             // node_handle now only has references/objects that point to itself,
             // so it's safe to drop
-            replace(&mut (*node_handle).data, None).unwrap()
+            (*node_handle).data.take().unwrap()
         }
     }
 
@@ -183,17 +178,17 @@ impl<T> Cache<T> {
     /// among several users.
     pub fn new_cache_id(&mut self) -> CacheID {
         self.id += 1;
-        return self.id;
+        self.id
     }
 
     /// How many the cache currently contains
     pub fn count(&self) -> usize {
-        return self.list.count();
+        self.list.count()
     }
 
     /// The capacity of this cache
     pub fn cap(&self) -> usize {
-        return self.cap;
+        self.cap
     }
 
     /// Insert a new element into the cache. The returned `CacheHandle` can be used for further
@@ -209,8 +204,8 @@ impl<T> Cache<T> {
             }
         }
 
-        let lru_handle = self.list.insert(key.clone());
-        self.map.insert(key.clone(), (elem, lru_handle));
+        let lru_handle = self.list.insert(*key);
+        self.map.insert(*key, (elem, lru_handle));
     }
 
     /// Retrieve an element from the cache.
@@ -218,7 +213,7 @@ impl<T> Cache<T> {
     pub fn get<'a>(&'a mut self, key: &CacheKey) -> Option<&'a T> {
         match self.map.get(key) {
             None => None,
-            Some(&(ref elem, ref lru_handle)) => {
+            Some((elem, lru_handle)) => {
                 self.list.reinsert_front(*lru_handle);
                 Some(elem)
             }
@@ -360,19 +355,19 @@ mod tests {
         let handle2 = lru.insert(22);
         let handle3 = lru.insert(244);
 
-        assert_eq!(lru._testing_head_ref().map(|r| (*r)).unwrap(), 244);
+        assert_eq!(lru._testing_head_ref().copied().unwrap(), 244);
 
         lru.reinsert_front(handle1);
 
-        assert_eq!(lru._testing_head_ref().map(|r| (*r)).unwrap(), 56);
+        assert_eq!(lru._testing_head_ref().copied().unwrap(), 56);
 
         lru.reinsert_front(handle3);
 
-        assert_eq!(lru._testing_head_ref().map(|r| (*r)).unwrap(), 244);
+        assert_eq!(lru._testing_head_ref().copied().unwrap(), 244);
 
         lru.reinsert_front(handle2);
 
-        assert_eq!(lru._testing_head_ref().map(|r| (*r)).unwrap(), 22);
+        assert_eq!(lru._testing_head_ref().copied().unwrap(), 22);
 
         assert_eq!(lru.remove_last(), Some(56));
         assert_eq!(lru.remove_last(), Some(244));
@@ -395,9 +390,9 @@ mod tests {
             lru.insert(8),
         ];
 
-        for i in 0..9 {
-            lru.reinsert_front(handles[i]);
-            assert_eq!(lru._testing_head_ref().map(|x| *x), Some(i));
+        for (i, handle) in handles.iter().enumerate() {
+            lru.reinsert_front(*handle);
+            assert_eq!(lru._testing_head_ref().copied(), Some(i));
         }
     }
 
@@ -408,7 +403,7 @@ mod tests {
         let handle = lru.insert(3);
 
         lru.reinsert_front(handle);
-        assert_eq!(lru._testing_head_ref().map(|x| *x), Some(3));
+        assert_eq!(lru._testing_head_ref().copied(), Some(3));
         assert_eq!(lru.remove_last(), Some(3));
         assert_eq!(lru.remove_last(), None);
         assert_eq!(lru.remove_last(), None);
@@ -1,4 +1,7 @@
+use crate::key_types::{self, ValueType, MAX_SEQUENCE_NUMBER};
+
 use std::cmp::Ordering;
+use std::sync::Arc;
 
 /// Comparator trait, supporting types that can be nested (i.e., add additional functionality on
 /// top of an inner comparator)
@@ -61,7 +64,7 @@ impl Cmp for DefaultCmp {
         sep.extend_from_slice(a);
         // Append a 0 byte; by making it longer than a, it will compare greater to it.
         sep.extend_from_slice(&[0]);
-        return sep;
+        sep
     }
 
     fn find_short_succ(&self, a: &[u8]) -> Vec<u8> {
@@ -75,7 +78,63 @@ impl Cmp for DefaultCmp {
         }
         // Rare path
         result.push(255);
-        return result;
+        result
+    }
+}
+
+const INTERNAL_KEY_CMP_ID: &str = "leveldb.InternalKeyComparator";
+
+/// Returns true if `cmp` is an InternalKeyCmp, i.e. the keys it orders are internal keys.
+pub(crate) fn is_internal_key_cmp(cmp: &dyn Cmp) -> bool {
+    cmp.id() == INTERNAL_KEY_CMP_ID
+}
+
+/// InternalKeyCmp orders internal keys (see the `key_types` module): by user key according to the
+/// wrapped comparator, then by descending sequence number and value type, so that the newest
+/// version of a key comes first.
+#[derive(Clone)]
+pub struct InternalKeyCmp(pub Arc<Box<dyn Cmp>>);
+
+impl InternalKeyCmp {
+    pub fn new(user_cmp: Arc<Box<dyn Cmp>>) -> InternalKeyCmp {
+        InternalKeyCmp(user_cmp)
+    }
+}
+
+impl Cmp for InternalKeyCmp {
+    fn cmp(&self, a: &[u8], b: &[u8]) -> Ordering {
+        let (ukey_a, tag_a) = key_types::split_internal_key(a);
+        let (ukey_b, tag_b) = key_types::split_internal_key(b);
+        match self.0.cmp(ukey_a, ukey_b) {
+            Ordering::Equal => tag_b.cmp(&tag_a),
+            o => o,
+        }
+    }
+
+    /// Internal keys aren't ordered like their user keys, so this isn't the inner comparator's
+    /// id.
+    fn id(&self) -> &'static str {
+        INTERNAL_KEY_CMP_ID
+    }
+
+    fn find_shortest_sep(&self, a: &[u8], b: &[u8]) -> Vec<u8> {
+        let (ukey_a, _) = key_types::split_internal_key(a);
+        let (ukey_b, _) = key_types::split_internal_key(b);
+        let sep = self.0.find_shortest_sep(ukey_a, ukey_b);
+        // The smallest internal key with the separating user key.
+        if self.0.cmp(ukey_a, &sep) == Ordering::Less {
+            return key_types::build_internal_key(&sep, MAX_SEQUENCE_NUMBER, ValueType::TypeValue);
+        }
+        a.to_vec()
+    }
+
+    fn find_short_succ(&self, a: &[u8]) -> Vec<u8> {
+        let (ukey_a, _) = key_types::split_internal_key(a);
+        let succ = self.0.find_short_succ(ukey_a);
+        if self.0.cmp(ukey_a, &succ) == Ordering::Less {
+            return key_types::build_internal_key(&succ, MAX_SEQUENCE_NUMBER, ValueType::TypeValue);
+        }
+        a.to_vec()
     }
 }
 
@@ -135,4 +194,34 @@ mod tests {
             &[0xff, 0xff, 0xff, 0xff]
         );
     }
+
+    #[test]
+    fn test_cmp_internalkeycmp() {
+        let cmp = InternalKeyCmp::new(Arc::new(Box::new(DefaultCmp)));
+        let key =
+            |k: &str, seq| key_types::build_internal_key(k.as_bytes(), seq, ValueType::TypeValue);
+        let del = key_types::build_internal_key(b"abc", 5, ValueType::TypeDeletion);
+
+        // Newer versions come first.
+        assert_eq!(cmp.cmp(&key("abc", 2), &key("abc", 1)), Ordering::Less);
+        assert_eq!(cmp.cmp(&key("abc", 5), &del), Ordering::Less);
+        assert_eq!(cmp.cmp(&key("abc", 1), &key("abd", 2)), Ordering::Less);
+        assert_eq!(cmp.cmp(&key("ab", 1), &key("abc", 2)), Ordering::Less);
+        assert_eq!(cmp.cmp(&key("abc", 1), &key("abc", 1)), Ordering::Equal);
+
+        let sep = cmp.find_shortest_sep(&key("abc", 1), &key("abe", 7));
+        assert_eq!(sep, key("abd", MAX_SEQUENCE_NUMBER));
+        // The same user key can't be separated.
+        let sep = cmp.find_shortest_sep(&key("abc", 7), &key("abc", 1));
+        assert_eq!(sep, key("abc", 7));
+        for (a, b) in [(key("abc", 1), key("abd", 9)), (key("a", 1), key("zzz", 3))] {
+            let sep = cmp.find_shortest_sep(&a, &b);
+            assert_eq!(cmp.cmp(&a, &sep), Ordering::Less);
+            assert_eq!(cmp.cmp(&sep, &b), Ordering::Less);
+        }
+
+        let succ = cmp.find_short_succ(&key("abc", 1));
+        assert_eq!(succ, key("b", MAX_SEQUENCE_NUMBER));
+        assert_eq!(cmp.cmp(&key("abc", 1), &succ), Ordering::Less);
+    }
 }
@@ -0,0 +1,417 @@
+use crate::cmp;
+use crate::error::{err, Result, StatusCode};
+use crate::key_types;
+use crate::merging_iter::MergingIter;
+use crate::options::ReadOptions;
+use crate::splitting_table_builder::{SplittingTableBuilder, TableInfo};
+use crate::table_reader::Table;
+use crate::types::SSIterator;
+
+use std::cmp::Ordering;
+use std::io::Write;
+
+/// Merges the entries of `inputs` into the tables written by `output`, and returns information on
+/// the written tables.
+///
+/// If a key is contained in more than one input, only the entry from the first of these inputs
+/// is used; i.e. inputs are ordered from highest to lowest priority (for example, newest to
+/// oldest). Entries for which `keep(key, value)` returns false are dropped; this happens after
+/// resolving duplicate keys, so that dropping an entry (e.g. a tombstone) also drops the entries
+/// it shadows.
+///
+/// Range deletions of the inputs (see `TableBuilder::delete_range()`) are applied to the entries,
+/// and added to every output table. In tables with internal keys, they delete the versions with a
+/// smaller sequence number in all inputs. Otherwise, the ranges of an input delete the entries of
+/// that input and of the following (older) inputs; as an output table can't express that a range
+/// doesn't apply to an entry from a newer input, a `NotSupported` error is returned if an entry
+/// that is kept is in a range of an older input.
+///
+/// Data blocks of the inputs are not added to the block cache. If an input can't be read
+/// completely, an error is returned and the last output table is not finished; the tables
+/// written so far must be discarded.
+pub fn merge_tables<Dst, F, K>(
+    inputs: &[Table],
+    mut output: SplittingTableBuilder<Dst, F>,
+    mut keep: K,
+) -> Result<Vec<TableInfo>>
+where
+    Dst: Write,
+    F: FnMut(usize) -> Result<Dst>,
+    K: FnMut(&[u8], &[u8]) -> bool,
+{
+    let ropt = ReadOptions {
+        fill_cache: false,
+        ..ReadOptions::default()
+    };
+    let iters = inputs
+        .iter()
+        .map(|t| Box::new(t.iter_with_options(&ropt)) as Box<dyn SSIterator>)
+        .collect();
+    let cmp = output.options().cmp.clone();
+    let mut merged = MergingIter::new(cmp.clone(), iters);
+
+    let internal_keys = cmp::is_internal_key_cmp(cmp.as_ref().as_ref());
+    for (start, end) in inputs.iter().flat_map(|t| t.range_deletions()) {
+        if internal_keys {
+            let (start, tag) = key_types::split_internal_key(start);
+            let (end, _) = key_types::split_internal_key(end);
+            output.delete_range_at(start, end, tag >> 8);
+        } else {
+            output.delete_range(start, end);
+        }
+    }
+
+    let (mut key, mut val) = (vec![], vec![]);
+    let mut last_key: Option<Vec<u8>> = None;
+    while merged.advance() {
+        // An input that failed would be missing from the output from here on.
+        merged.status()?;
+        if !merged.current(&mut key, &mut val) {
+            return err(
+                StatusCode::Corruption,
+                "couldn't read entry from input table",
+            );
+        }
+        // Entries with equal keys come in the order of the inputs.
+        if let Some(ref last) = last_key {
+            if cmp.cmp(last, &key) == Ordering::Equal {
+                continue;
+            }
+        }
+        last_key = Some(key.clone());
+
+        let source = merged.current_source().unwrap();
+        let deleted_by = if internal_keys {
+            inputs
+        } else {
+            &inputs[..=source]
+        };
+        if deleted_by.iter().any(|t| t.is_range_deleted(&key)) || !keep(&key, &val) {
+            continue;
+        }
+        if !internal_keys
+            && inputs[source + 1..]
+                .iter()
+                .any(|t| t.is_range_deleted(&key))
+        {
+            return err(
+                StatusCode::NotSupported,
+                "range deletion of an older input covers an entry of a newer input",
+            );
+        }
+        output.add(&key, &val)?;
+    }
+    merged.status()?;
+    output.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_types::{
+        build_internal_key, parse_internal_key, LookupResult, SequenceNumber, ValueType,
+    };
+    use crate::options::Options;
+    use crate::table_builder::TableBuilder;
+    use crate::test_util::SSIteratorIter;
+
+    fn build_table(opt: &Options, entries: &[(String, String)]) -> Table {
+        let mut dst = vec![];
+        {
+            let mut b = TableBuilder::new(opt.clone(), &mut dst);
+            for (k, v) in entries.iter() {
+                b.add(k.as_bytes(), v.as_bytes()).unwrap();
+            }
+            b.finish().unwrap();
+        }
+        let size = dst.len();
+        Table::new(opt.clone(), Box::new(dst), size).unwrap()
+    }
+
+    #[test]
+    fn test_merge_tables() {
+        let mut opt = Options::default();
+        opt.block_size = 128;
+
+        // The newest table contains every third key, the oldest one all keys; the newest table
+        // contains tombstones for every 15th key.
+        let newest: Vec<(String, String)> = (0..300)
+            .step_by(3)
+            .map(|i| {
+                let val = if i % 5 == 0 { "" } else { "new" };
+                (format!("key{:03}", i), val.to_string())
+            })
+            .collect();
+        let middle: Vec<(String, String)> = (0..300)
+            .step_by(2)
+            .map(|i| (format!("key{:03}", i), "middle".to_string()))
+            .collect();
+        let oldest: Vec<(String, String)> = (0..300)
+            .map(|i| (format!("key{:03}", i), "old".to_string()))
+            .collect();
+        let inputs = vec![
+            build_table(&opt, &newest),
+            build_table(&opt, &middle),
+            build_table(&opt, &oldest),
+        ];
+
+        let mut created = 0;
+        let tables = {
+            let output = SplittingTableBuilder::new(opt.clone(), 2048, |_| {
+                created += 1;
+                Ok(std::io::sink())
+            });
+            merge_tables(&inputs, output, |_, v| !v.is_empty()).unwrap()
+        };
+        assert_eq!(created, tables.len());
+        assert!(tables.len() > 1);
+        assert_eq!(tables.iter().map(|t| t.entries).sum::<usize>(), 300 - 20);
+        assert_eq!(tables[0].smallest_key, b"key001");
+        assert_eq!(tables.last().unwrap().largest_key, b"key299");
+
+        // Write everything into one table to check the contents.
+        let mut dst = vec![];
+        {
+            let mut dst = Some(&mut dst);
+            let output =
+                SplittingTableBuilder::new(opt.clone(), usize::MAX, |_| Ok(dst.take().unwrap()));
+            merge_tables(&inputs, output, |_, v| !v.is_empty()).unwrap();
+        }
+        let size = dst.len();
+        let merged = Table::new(opt, Box::new(dst), size).unwrap();
+        let mut iter = merged.iter();
+        let expected = (0..300)
+            .filter(|i| i % 15 != 0)
+            .map(|i| {
+                let val = if i % 3 == 0 {
+                    "new"
+                } else if i % 2 == 0 {
+                    "middle"
+                } else {
+                    "old"
+                };
+                (format!("key{:03}", i).into_bytes(), val.as_bytes().to_vec())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            SSIteratorIter::wrap(&mut iter).collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    fn build_table_with_ranges(
+        opt: &Options,
+        entries: &[(Vec<u8>, Vec<u8>)],
+        ranges: &[(&[u8], &[u8], SequenceNumber)],
+    ) -> Table {
+        let mut dst = vec![];
+        {
+            let mut b = TableBuilder::new(opt.clone(), &mut dst);
+            for (k, v) in entries.iter() {
+                b.add(k, v).unwrap();
+            }
+            for &(start, end, seq) in ranges.iter() {
+                b.delete_range_at(start, end, seq);
+            }
+            b.finish().unwrap();
+        }
+        let size = dst.len();
+        Table::new(opt.clone(), Box::new(dst), size).unwrap()
+    }
+
+    /// Merges `inputs` into a single table.
+    fn merge_into_table(opt: &Options, inputs: &[Table]) -> Result<Table> {
+        let mut dst = vec![];
+        {
+            let mut dst = Some(&mut dst);
+            let output =
+                SplittingTableBuilder::new(opt.clone(), usize::MAX, |_| Ok(dst.take().unwrap()));
+            merge_tables(inputs, output, |_, _| true)?;
+        }
+        let size = dst.len();
+        Table::new(opt.clone(), Box::new(dst), size)
+    }
+
+    #[test]
+    fn test_merge_tables_range_deletions() {
+        let opt = Options::default();
+        let entries = |keys: &[&str], val: &str| {
+            keys.iter()
+                .map(|k| (k.as_bytes().to_vec(), val.as_bytes().to_vec()))
+                .collect::<Vec<_>>()
+        };
+        // The range of the newest input deletes "b" and "c" in the older inputs, the range of the
+        // middle input its own "f" and the oldest input's "g".
+        let inputs = vec![
+            build_table_with_ranges(&opt, &entries(&["a", "d"], "new"), &[(b"b", b"d", 0)]),
+            build_table_with_ranges(&opt, &entries(&["b", "f"], "middle"), &[(b"f", b"h", 0)]),
+            build_table_with_ranges(&opt, &entries(&["c", "e", "g", "h"], "old"), &[]),
+        ];
+        let merged = merge_into_table(&opt, &inputs).unwrap();
+        let mut iter = merged.iter();
+        assert_eq!(
+            SSIteratorIter::wrap(&mut iter).collect::<Vec<_>>(),
+            vec![
+                (b"a".to_vec(), b"new".to_vec()),
+                (b"d".to_vec(), b"new".to_vec()),
+                (b"e".to_vec(), b"old".to_vec()),
+                (b"h".to_vec(), b"old".to_vec()),
+            ]
+        );
+        // The ranges still apply to older tables.
+        assert_eq!(
+            merged.range_deletions(),
+            &[
+                (b"b".to_vec(), b"d".to_vec()),
+                (b"f".to_vec(), b"h".to_vec())
+            ]
+        );
+
+        // "f" of the middle input can't be kept in a range of the oldest input.
+        let inputs = vec![
+            build_table_with_ranges(&opt, &entries(&["a"], "new"), &[]),
+            build_table_with_ranges(&opt, &entries(&["f"], "middle"), &[]),
+            build_table_with_ranges(&opt, &entries(&["g"], "old"), &[(b"e", b"g", 0)]),
+        ];
+        assert_eq!(
+            merge_into_table(&opt, &inputs).err().unwrap().code,
+            StatusCode::NotSupported
+        );
+
+        // Only range deletions.
+        let inputs = vec![
+            build_table_with_ranges(&opt, &[], &[(b"a", b"c", 0)]),
+            build_table_with_ranges(&opt, &entries(&["b"], "old"), &[]),
+        ];
+        let merged = merge_into_table(&opt, &inputs).unwrap();
+        assert_eq!(merged.iter().next(), None);
+        assert_eq!(merged.range_deletions(), &[(b"a".to_vec(), b"c".to_vec())]);
+    }
+
+    #[test]
+    fn test_merge_tables_range_deletions_internal_keys() {
+        let opt = Options::default().with_internal_keys();
+        let user_key = |i: usize| format!("user-key-{:02}", i).into_bytes();
+        let versions = |seqs: &[SequenceNumber]| {
+            let mut entries = vec![];
+            for i in 0..10 {
+                for &seq in seqs.iter() {
+                    entries.push((
+                        build_internal_key(&user_key(i), seq, ValueType::TypeValue),
+                        format!("{}@{}", i, seq).into_bytes(),
+                    ));
+                }
+            }
+            entries
+        };
+        // The newer input deletes versions before 25 of keys 2 to 4, including its own version
+        // 20; the older input deletes versions before 15 of keys 3 to 6, but not the newer
+        // input's versions.
+        let inputs = vec![
+            build_table_with_ranges(
+                &opt,
+                &versions(&[30, 20]),
+                &[(&user_key(2), &user_key(5), 25)],
+            ),
+            build_table_with_ranges(&opt, &versions(&[10]), &[(&user_key(3), &user_key(7), 15)]),
+        ];
+        let merged = merge_into_table(&opt, &inputs).unwrap();
+        let mut iter = merged.iter();
+        let expected: Vec<_> = versions(&[30, 20, 10])
+            .into_iter()
+            .filter(|(k, _)| {
+                let (key, seq, _) = parse_internal_key(k).unwrap();
+                match key[9..].iter().fold(0, |n, d| n * 10 + (d - b'0') as usize) {
+                    2..=4 => seq >= 25,
+                    5 | 6 => seq >= 15,
+                    _ => true,
+                }
+            })
+            .collect();
+        assert_eq!(
+            SSIteratorIter::wrap(&mut iter).collect::<Vec<_>>(),
+            expected
+        );
+
+        // The ranges are kept, with their sequence numbers.
+        assert_eq!(merged.range_deletions().len(), 2);
+        assert_eq!(
+            merged.get_at(&user_key(3), 27),
+            Ok(Some(LookupResult::Deleted { seq: 25 }))
+        );
+        assert_eq!(
+            merged.get_at(&user_key(6), 17),
+            Ok(Some(LookupResult::Deleted { seq: 15 }))
+        );
+        assert_eq!(
+            merged.get_at(&user_key(6), 22),
+            Ok(Some(LookupResult::Value {
+                seq: 20,
+                value: b"6@20".to_vec()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_merge_tables_input_error() {
+        let mut opt = Options::default();
+        opt.blob_value_threshold = 10;
+
+        // The blob of key050 is broken, so the input can't be read past it.
+        let entries: Vec<(String, String)> = (0..100)
+            .map(|i| (format!("key{:03}", i), format!("value{:010}", i)))
+            .collect();
+        let (mut dst, mut blob_dst) = (vec![], vec![]);
+        {
+            let mut b = TableBuilder::new_with_blob_file(opt.clone(), &mut dst, &mut blob_dst);
+            for (k, v) in entries.iter() {
+                b.add(k.as_bytes(), v.as_bytes()).unwrap();
+            }
+            b.finish().unwrap();
+        }
+        let mid = blob_dst.len() / 2;
+        blob_dst[mid] ^= 1;
+        let size = dst.len();
+        let broken =
+            Table::new_with_blob_file(opt.clone(), Box::new(dst), size, Box::new(blob_dst))
+                .unwrap();
+        let inputs = vec![build_table(&opt, &entries[..10]), broken];
+
+        let mut added = 0;
+        let output = SplittingTableBuilder::new(opt, usize::MAX, |_| Ok(std::io::sink()));
+        let result = merge_tables(&inputs, output, |_, _| {
+            added += 1;
+            true
+        });
+        assert_eq!(result.unwrap_err().code, StatusCode::Corruption);
+        assert_eq!(added, 50);
+    }
+
+    #[test]
+    fn test_merge_tables_corrupted_block() {
+        let mut opt = Options::default();
+        opt.block_size = 128;
+
+        // A data block in the middle of the second input is corrupted.
+        let entries: Vec<(String, String)> = (0..100)
+            .map(|i| (format!("key{:03}", i), format!("value{:03}", i)))
+            .collect();
+        let mut dst = vec![];
+        {
+            let mut b = TableBuilder::new(opt.clone(), &mut dst);
+            for (k, v) in entries.iter() {
+                b.add(k.as_bytes(), v.as_bytes()).unwrap();
+            }
+            b.finish().unwrap();
+        }
+        let mid = dst.len() / 3;
+        dst[mid] ^= 1;
+        let size = dst.len();
+        let broken = Table::new(opt.clone(), Box::new(dst), size).unwrap();
+        let inputs = vec![build_table(&opt, &entries[..10]), broken];
+
+        let output = SplittingTableBuilder::new(opt, usize::MAX, |_| Ok(std::io::sink()));
+        let result = merge_tables(&inputs, output, |_, _| true);
+        assert_eq!(result.unwrap_err().code, StatusCode::Corruption);
+    }
+}
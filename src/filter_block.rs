@@ -6,12 +6,15 @@ use std::sync::Arc;
 use integer_encoding::FixedInt;
 
 const FILTER_BASE_LOG2: u32 = 11;
+/// A full filter block contains a single filter for all keys of the table; it is a normal filter
+/// block, with a filter base large enough for every block offset to map to the first filter.
+const FULL_FILTER_BASE_LOG2: u32 = 63;
 
 /// For a given byte offset, returns the index of the filter that includes the key at that offset.
 #[inline]
 fn get_filter_index(offset: usize, base_lg2: u32) -> u32 {
     // divide by 2048
-    (offset >> base_lg2 as usize) as u32
+    offset.checked_shr(base_lg2).unwrap_or(0) as u32
 }
 
 /// A Filter Block is built like this:
@@ -25,6 +28,7 @@ fn get_filter_index(offset: usize, base_lg2: u32) -> u32 {
 /// TODO: See if we can remove the lifetime parameter.
 pub struct FilterBlockBuilder {
     policy: BoxedFilterPolicy,
+    filter_base_lg2: u32,
     // filters, concatenated
     filters: Vec<u8>,
     filter_offsets: Vec<usize>,
@@ -36,8 +40,19 @@ pub struct FilterBlockBuilder {
 
 impl FilterBlockBuilder {
     pub fn new(fp: BoxedFilterPolicy) -> FilterBlockBuilder {
+        FilterBlockBuilder::with_base_lg2(fp, FILTER_BASE_LOG2)
+    }
+
+    /// Returns a builder for a full filter block, which holds all keys added to it in memory
+    /// until `finish()` is called.
+    pub fn new_full(fp: BoxedFilterPolicy) -> FilterBlockBuilder {
+        FilterBlockBuilder::with_base_lg2(fp, FULL_FILTER_BASE_LOG2)
+    }
+
+    fn with_base_lg2(fp: BoxedFilterPolicy, base_lg2: u32) -> FilterBlockBuilder {
         FilterBlockBuilder {
             policy: fp,
+            filter_base_lg2: base_lg2,
             // some pre-allocation
             filters: Vec::with_capacity(1024),
             filter_offsets: Vec::with_capacity(1024),
@@ -60,7 +75,7 @@ impl FilterBlockBuilder {
     }
 
     pub fn start_block(&mut self, offset: usize) {
-        let filter_ix = get_filter_index(offset, FILTER_BASE_LOG2);
+        let filter_ix = get_filter_index(offset, self.filter_base_lg2);
         assert!(filter_ix >= self.filter_offsets.len() as u32);
 
         while filter_ix > self.filter_offsets.len() as u32 {
@@ -99,7 +114,7 @@ impl FilterBlockBuilder {
 
         (offsets_offset as u32).encode_fixed(&mut result[ix..ix + 4]);
         ix += 4;
-        result[ix] = FILTER_BASE_LOG2 as u8;
+        result[ix] = self.filter_base_lg2 as u8;
 
         result
     }
@@ -133,6 +148,12 @@ impl FilterBlockReader {
         }
     }
 
+    /// Returns whether this is a full filter block, i.e. there's a single filter for all keys of
+    /// the table.
+    pub fn is_full(&self) -> bool {
+        self.filter_base_lg2 == FULL_FILTER_BASE_LOG2
+    }
+
     /// Returns number of filters
     pub fn num(&self) -> u32 {
         ((self.block.len() - self.offsets_offset - 5) / 4) as u32
@@ -220,6 +241,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_filter_block_full() {
+        let policy: BoxedFilterPolicy = Arc::new(Box::new(BloomPolicy::new(32)));
+        let mut bld = FilterBlockBuilder::new_full(policy.clone());
+
+        bld.start_block(0);
+        bld.add_key(get_keys()[0]);
+        bld.add_key(get_keys()[1]);
+        bld.start_block(5000);
+        bld.add_key(get_keys()[2]);
+        bld.start_block(1 << 40);
+        bld.add_key(get_keys()[3]);
+
+        let reader = FilterBlockReader::new_owned(policy, bld.finish());
+        assert!(reader.is_full());
+        assert_eq!(reader.num(), 1);
+
+        for block_offset in vec![0, 1024, 5000, 1 << 40].into_iter() {
+            for key in get_keys().iter() {
+                assert!(reader.key_may_match(block_offset, key));
+            }
+            assert!(!reader.key_may_match(block_offset, b"xsb"));
+        }
+    }
+
     #[test]
     fn test_filter_block_build_read() {
         let result = produce_filter_block();
@@ -230,7 +276,7 @@ mod tests {
             17
         ); // third block in third filter
 
-        let unknown_keys = vec![
+        let unknown_keys = [
             "xsb".as_bytes(),
             "9sad".as_bytes(),
             "assssaaaass".as_bytes(),
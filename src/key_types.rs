@@ -0,0 +1,90 @@
+//! Internal keys, which allow a table to contain several versions of a key, and deletions.
+//!
+//! An internal key consists of the user key followed by a fixed u64 TAG, which is
+//! `sequence number << 8 | value type`. Tables with internal keys must be written and read with
+//! `Options::with_internal_keys()`, which orders keys by user key, then by descending sequence
+//! number; look-ups are done with `Table::get_at()`.
+
+use integer_encoding::FixedInt;
+
+pub type SequenceNumber = u64;
+
+/// The largest sequence number that can be encoded in an internal key.
+pub const MAX_SEQUENCE_NUMBER: SequenceNumber = (1 << 56) - 1;
+
+const TAG_LEN: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ValueType {
+    TypeDeletion = 0,
+    TypeValue = 1,
+}
+
+/// LookupResult is the newest version of a key visible at a sequence number; see
+/// `Table::get_at()`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LookupResult {
+    Value {
+        seq: SequenceNumber,
+        value: Vec<u8>,
+    },
+    /// The key has been deleted.
+    Deleted {
+        seq: SequenceNumber,
+    },
+}
+
+/// Returns the internal key for version `seq` of `user_key`.
+pub fn build_internal_key(user_key: &[u8], seq: SequenceNumber, t: ValueType) -> Vec<u8> {
+    assert!(seq <= MAX_SEQUENCE_NUMBER);
+    let mut key = Vec::with_capacity(user_key.len() + TAG_LEN);
+    key.extend_from_slice(user_key);
+    key.extend_from_slice(&(seq << 8 | t as u64).encode_fixed_vec());
+    key
+}
+
+/// Splits an internal key into user key, sequence number and value type. Returns None if `key`
+/// isn't a valid internal key.
+pub fn parse_internal_key(key: &[u8]) -> Option<(&[u8], SequenceNumber, ValueType)> {
+    if key.len() < TAG_LEN {
+        return None;
+    }
+    let (user_key, tag) = split_internal_key(key);
+    let t = match tag & 0xff {
+        0 => ValueType::TypeDeletion,
+        1 => ValueType::TypeValue,
+        _ => return None,
+    };
+    Some((user_key, tag >> 8, t))
+}
+
+/// Splits an internal key into user key and tag. Keys too short to be internal keys (e.g. those
+/// of meta blocks) are treated as user keys with a tag of 0.
+pub fn split_internal_key(key: &[u8]) -> (&[u8], u64) {
+    if key.len() < TAG_LEN {
+        return (key, 0);
+    }
+    let (user_key, tag) = key.split_at(key.len() - TAG_LEN);
+    (user_key, u64::decode_fixed(tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_internal_key_roundtrip() {
+        for &(key, seq, t) in &[
+            (&b"abc"[..], 0, ValueType::TypeValue),
+            (b"", 17, ValueType::TypeDeletion),
+            (b"\xff\x00", MAX_SEQUENCE_NUMBER, ValueType::TypeValue),
+        ] {
+            let ikey = build_internal_key(key, seq, t);
+            assert_eq!(ikey.len(), key.len() + 8);
+            assert_eq!(parse_internal_key(&ikey), Some((key, seq, t)));
+        }
+        assert_eq!(parse_internal_key(b"short"), None);
+        assert_eq!(parse_internal_key(b"bad tag\x07\0\0\0\0\0\0\0"), None);
+        assert_eq!(split_internal_key(b"short"), (&b"short"[..], 0));
+    }
+}
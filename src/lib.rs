@@ -1,7 +1,11 @@
+// Tests customize `Options::default()` field by field.
+#![cfg_attr(test, allow(clippy::field_reassign_with_default))]
+
 #[cfg(test)]
 #[macro_use]
 extern crate time_test;
 
+mod blob;
 mod block;
 mod block_builder;
 mod blockhandle;
@@ -9,19 +13,41 @@ mod cache;
 pub mod error;
 pub mod filter;
 mod filter_block;
+mod key_types;
+mod merging_iter;
 mod table_block;
 mod types;
 
 mod cmp;
+mod compaction;
 mod options;
+mod properties;
+mod range_deletions;
+mod rocksdb;
+mod sorting_table_builder;
+mod splitting_table_builder;
 mod table_builder;
 mod table_reader;
 
-pub use crate::cmp::{Cmp, DefaultCmp};
+pub use crate::cmp::{Cmp, DefaultCmp, InternalKeyCmp};
+pub use crate::compaction::merge_tables;
 pub use crate::error::{Result, Status, StatusCode};
-pub use crate::options::{CompressionType, Options};
+pub use crate::key_types::{
+    build_internal_key, parse_internal_key, LookupResult, SequenceNumber, ValueType,
+    MAX_SEQUENCE_NUMBER,
+};
+pub use crate::merging_iter::MergingIter;
+pub use crate::options::{
+    BlockFormat, ChecksumType, CompressionType, DataBlockIndexType, FilterType, IndexType, Options,
+    ReadOptions,
+};
+pub use crate::properties::TableProperties;
+pub use crate::sorting_table_builder::{DuplicateKeyPolicy, SortingTableBuilder};
+pub use crate::splitting_table_builder::{SplittingTableBuilder, TableInfo};
 pub use crate::table_builder::TableBuilder;
-pub use crate::table_reader::{Table, TableIterator};
+pub use crate::table_reader::{
+    BlockInfo, FilterStats, MetaBlockInfo, Table, TableIterator, TableRange, TableStats,
+};
 pub use crate::types::{current_key_val, RandomAccess, SSIterator};
 
 #[cfg(test)]
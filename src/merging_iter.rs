@@ -0,0 +1,297 @@
+use crate::cmp::Cmp;
+use crate::error::Result;
+use crate::types::SSIterator;
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+#[derive(PartialEq)]
+enum Direction {
+    Forward,
+    Reverse,
+}
+
+/// MergingIter merges several sorted iterators into a single sorted iterator. Entries with equal
+/// keys are all returned, in the order of the iterators they come from (i.e. the entry from the
+/// first iterator comes first when iterating forward). Each iterator must not contain a key more
+/// than once.
+pub struct MergingIter {
+    iters: Vec<Box<dyn SSIterator>>,
+    current: Option<usize>,
+    direction: Direction,
+    cmp: Arc<Box<dyn Cmp>>,
+}
+
+impl MergingIter {
+    /// Creates a new merging iterator over `iters`, which must be sorted according to `cmp`.
+    pub fn new(cmp: Arc<Box<dyn Cmp>>, iters: Vec<Box<dyn SSIterator>>) -> MergingIter {
+        MergingIter {
+            iters,
+            current: None,
+            direction: Direction::Forward,
+            cmp,
+        }
+    }
+
+    /// Returns the index of the iterator the current entry comes from.
+    pub fn current_source(&self) -> Option<usize> {
+        self.current.filter(|_| self.valid())
+    }
+
+    fn init(&mut self) {
+        for it in self.iters.iter_mut() {
+            it.reset();
+            it.advance();
+        }
+        self.find_smallest();
+    }
+
+    /// Adjusts the state of all sub-iterators so that they are positioned after the current
+    /// entry, in preparation for a forward step after having moved backwards.
+    fn update_after_reverse(&mut self) {
+        let current = self.current.unwrap();
+        let key = self.iters[current].current_key().unwrap().to_vec();
+        for (i, it) in self.iters.iter_mut().enumerate() {
+            if i == current {
+                continue;
+            }
+            it.seek(&key);
+            // Entries with the same key from earlier iterators come before the current entry.
+            if i < current {
+                while let Some(k) = it.current_key() {
+                    if self.cmp.cmp(k, &key) != Ordering::Equal {
+                        break;
+                    }
+                    it.advance();
+                }
+            }
+        }
+        self.direction = Direction::Forward;
+    }
+
+    /// Adjusts the state of all sub-iterators so that they are positioned before the current
+    /// entry, in preparation for a backward step after having moved forward.
+    fn update_after_forward(&mut self) {
+        let current = self.current.unwrap();
+        let key = self.iters[current].current_key().unwrap().to_vec();
+        for (i, it) in self.iters.iter_mut().enumerate() {
+            if i == current {
+                continue;
+            }
+            it.seek(&key);
+            // An entry with the same key from an earlier iterator comes before the current entry.
+            if i < current {
+                if let Some(k) = it.current_key() {
+                    if self.cmp.cmp(k, &key) == Ordering::Equal {
+                        continue;
+                    }
+                }
+            }
+            if it.valid() {
+                it.prev();
+            } else {
+                seek_to_last(it.as_mut());
+            }
+        }
+        self.direction = Direction::Reverse;
+    }
+
+    fn find_smallest(&mut self) {
+        let mut smallest: Option<usize> = None;
+        for (i, it) in self.iters.iter().enumerate() {
+            if let Some(key) = it.current_key() {
+                if let Some(s) = smallest {
+                    let skey = self.iters[s].current_key().unwrap();
+                    if self.cmp.cmp(key, skey) == Ordering::Less {
+                        smallest = Some(i);
+                    }
+                } else {
+                    smallest = Some(i);
+                }
+            }
+        }
+        self.current = smallest;
+    }
+
+    fn find_largest(&mut self) {
+        let mut largest: Option<usize> = None;
+        for (i, it) in self.iters.iter().enumerate() {
+            if let Some(key) = it.current_key() {
+                if let Some(l) = largest {
+                    let lkey = self.iters[l].current_key().unwrap();
+                    if self.cmp.cmp(key, lkey) != Ordering::Less {
+                        largest = Some(i);
+                    }
+                } else {
+                    largest = Some(i);
+                }
+            }
+        }
+        self.current = largest;
+    }
+}
+
+/// Positions `it` at its last entry. SSIterator has no efficient way of doing this, so the whole
+/// iterator is scanned.
+fn seek_to_last(it: &mut dyn SSIterator) {
+    let mut last = None;
+    it.reset();
+    while it.advance() {
+        last = it.current_key().map(|k| k.to_vec());
+    }
+    if let Some(last) = last {
+        it.seek(&last);
+    }
+}
+
+impl SSIterator for MergingIter {
+    fn advance(&mut self) -> bool {
+        if let Some(current) = self.current {
+            if self.direction != Direction::Forward {
+                self.update_after_reverse();
+            }
+            self.iters[current].advance();
+            self.find_smallest();
+        } else {
+            self.init();
+        }
+        self.valid()
+    }
+
+    fn valid(&self) -> bool {
+        match self.current {
+            Some(c) => self.iters[c].valid(),
+            None => false,
+        }
+    }
+
+    fn seek(&mut self, key: &[u8]) {
+        for it in self.iters.iter_mut() {
+            it.seek(key);
+        }
+        self.direction = Direction::Forward;
+        self.find_smallest();
+    }
+
+    fn reset(&mut self) {
+        for it in self.iters.iter_mut() {
+            it.reset();
+        }
+        self.direction = Direction::Forward;
+        self.current = None;
+    }
+
+    fn current(&self, key: &mut Vec<u8>, val: &mut Vec<u8>) -> bool {
+        match self.current {
+            Some(c) => self.iters[c].current(key, val),
+            None => false,
+        }
+    }
+
+    fn current_key(&self) -> Option<&[u8]> {
+        self.current.and_then(|c| self.iters[c].current_key())
+    }
+
+    // Returns the first error of the merged iterators. The other iterators are still merged after
+    // one of them has failed, so check this before relying on the merged entries being complete.
+    fn status(&self) -> Result<()> {
+        self.iters.iter().try_for_each(|it| it.status())
+    }
+
+    fn prev(&mut self) -> bool {
+        let current = match self.current {
+            Some(c) if self.valid() => c,
+            _ => return false,
+        };
+        if self.direction != Direction::Reverse {
+            self.update_after_forward();
+        }
+        self.iters[current].prev();
+        self.find_largest();
+        if self.valid() {
+            true
+        } else {
+            self.reset();
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmp::DefaultCmp;
+    use crate::test_util::{test_iterator_properties, SSIteratorIter, TestSSIter};
+    use crate::types::current_key_val;
+
+    fn b(s: &'static str) -> &'static [u8] {
+        s.as_bytes()
+    }
+
+    fn merging_iter(iters: Vec<Vec<(&'static [u8], &'static [u8])>>) -> MergingIter {
+        MergingIter::new(
+            Arc::new(Box::new(DefaultCmp)),
+            iters
+                .into_iter()
+                .map(|v| Box::new(TestSSIter::new(v)) as Box<dyn SSIterator>)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_merging_iter_properties() {
+        let it = merging_iter(vec![
+            vec![(b("aba"), b("1")), (b("abc"), b("2"))],
+            vec![(b("abb"), b("3")), (b("abd"), b("4"))],
+        ]);
+        test_iterator_properties(it);
+    }
+
+    #[test]
+    fn test_merging_iter_duplicates() {
+        let mut it = merging_iter(vec![
+            vec![(b("a"), b("1")), (b("c"), b("1")), (b("d"), b("1"))],
+            vec![(b("b"), b("2")), (b("c"), b("2")), (b("e"), b("2"))],
+            vec![(b("c"), b("3")), (b("f"), b("3"))],
+        ]);
+
+        let expected: Vec<(Vec<u8>, Vec<u8>)> = [
+            ("a", "1"),
+            ("b", "2"),
+            ("c", "1"),
+            ("c", "2"),
+            ("c", "3"),
+            ("d", "1"),
+            ("e", "2"),
+            ("f", "3"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.as_bytes().to_vec(), v.as_bytes().to_vec()))
+        .collect();
+        assert_eq!(SSIteratorIter::wrap(&mut it).collect::<Vec<_>>(), expected);
+
+        // Go backwards from the end, and change direction in between.
+        it.seek(b("f"));
+        let mut backwards = vec![current_key_val(&it).unwrap()];
+        while it.prev() {
+            backwards.push(current_key_val(&it).unwrap());
+        }
+        backwards.reverse();
+        assert_eq!(backwards, expected);
+
+        it.seek(b("c"));
+        assert_eq!(it.current_source(), Some(0));
+        assert!(it.advance());
+        assert_eq!(current_key_val(&it), Some(expected[3].clone()));
+        assert!(it.prev());
+        assert_eq!(current_key_val(&it), Some(expected[2].clone()));
+        assert!(it.prev());
+        assert_eq!(current_key_val(&it), Some(expected[1].clone()));
+        assert!(it.advance());
+        assert!(it.advance());
+        assert_eq!(current_key_val(&it), Some(expected[3].clone()));
+        assert!(it.advance());
+        assert_eq!(current_key_val(&it), Some(expected[4].clone()));
+        assert_eq!(it.current_source(), Some(2));
+    }
+}
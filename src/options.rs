@@ -1,6 +1,6 @@
 use crate::block::Block;
 use crate::cache::Cache;
-use crate::cmp::{Cmp, DefaultCmp};
+use crate::cmp::{Cmp, DefaultCmp, InternalKeyCmp};
 use crate::filter;
 use crate::types::{share, Shared};
 
@@ -13,6 +13,7 @@ const MB: usize = KB * KB;
 const BLOCK_MAX_SIZE: usize = 4 * KB;
 const BLOCK_CACHE_CAPACITY: usize = 8 * MB;
 const WRITE_BUFFER_SIZE: usize = 4 * MB;
+const BLOB_VALUE_THRESHOLD: usize = KB;
 const DEFAULT_BITS_PER_KEY: u32 = 10; // NOTE: This may need to be optimized.
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -29,6 +30,79 @@ pub fn int_to_compressiontype(i: u32) -> Option<CompressionType> {
     }
 }
 
+/// ChecksumType determines how the checksums in block trailers are computed. The values are the
+/// ones used by RocksDB.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum ChecksumType {
+    /// Blocks aren't checksummed; only found in tables written by RocksDB.
+    NoChecksum = 0,
+    /// Masked CRC32C, as in LevelDB.
+    #[default]
+    Crc32c = 1,
+    /// xxHash (32 bit).
+    XxHash = 2,
+    /// The lower 32 bits of xxHash64.
+    XxHash64 = 3,
+    /// The lower 32 bits of XXH3 (64 bit). Usually the fastest one.
+    Xxh3 = 4,
+}
+
+pub fn int_to_checksumtype(i: u32) -> Option<ChecksumType> {
+    match i {
+        0 => Some(ChecksumType::NoChecksum),
+        1 => Some(ChecksumType::Crc32c),
+        2 => Some(ChecksumType::XxHash),
+        3 => Some(ChecksumType::XxHash64),
+        4 => Some(ChecksumType::Xxh3),
+        _ => None,
+    }
+}
+
+/// IndexType determines how the index of a table, which contains an entry per data block, is
+/// stored.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IndexType {
+    /// A single index block, which is read completely when looking up a key.
+    SingleLevel,
+    /// The index is split into partitions of about `block_size` bytes, and a top-level index block
+    /// points to them. Only the top-level index and the partitions needed for a lookup have to be
+    /// in memory. Tables with a partitioned index can't be read by LevelDB or older versions of
+    /// this crate.
+    Partitioned,
+}
+
+/// BlockFormat determines how the restart points of blocks are stored (see `block::Block`).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BlockFormat {
+    /// A fixed u32 per restart point, as in LevelDB.
+    Standard,
+    /// Varint-encoded differences between restart points, which saves space with small entries.
+    /// Tables with compact blocks can't be read by LevelDB or older versions of this crate.
+    Compact,
+}
+
+/// DataBlockIndexType determines how keys are looked up within a data block.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DataBlockIndexType {
+    /// A binary search over the restart points, followed by a linear search.
+    BinarySearch,
+    /// Data blocks additionally contain a hash index mapping keys to restart points, which is
+    /// used for point lookups (`Table::get()`). Seeks still use the binary search. Tables with a
+    /// hash index can't be read by LevelDB or older versions of this crate.
+    BinaryAndHash,
+}
+
+/// FilterType determines how the filter (e.g. bloom filter) of a table is laid out.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FilterType {
+    /// One filter for every 2 KiB of data blocks; a lookup checks the filter of the data block
+    /// found in the index block.
+    PerBlock,
+    /// A single filter for all keys in the table, which is checked before the index block. The
+    /// table builder keeps all keys in memory until the table is finished.
+    Full,
+}
+
 /// Options contains general parameters for reading and writing SSTables. Most of the names are
 /// self-explanatory; the defaults are defined in the `Default` implementation.
 #[derive(Clone)]
@@ -39,7 +113,45 @@ pub struct Options {
     pub block_size: usize,
     pub block_restart_interval: usize,
     pub compression_type: CompressionType,
+    /// The format of blocks in tables written with these options. When reading a table, its own
+    /// format is used.
+    pub block_format: BlockFormat,
+    /// The checksum type of blocks in tables written with these options. Tables with a checksum
+    /// type other than `ChecksumType::Crc32c` can't be read by LevelDB. When reading a table, its
+    /// own checksum type is used. `ChecksumType::NoChecksum` can only be read, not written.
+    pub checksum_type: ChecksumType,
+    pub index_type: IndexType,
+    pub data_block_index_type: DataBlockIndexType,
+    /// The ratio of keys to hash buckets in a data block hash index.
+    pub data_block_hash_ratio: f64,
+    /// The filter policy used for writing tables.
     pub filter_policy: filter::BoxedFilterPolicy,
+    /// Filter policies that can be used for reading tables, in addition to `filter_policy`. A
+    /// table's filter is used if its policy is `filter_policy` or one of these; by default, these
+    /// are all policies implemented in the `filter` module.
+    pub filter_policies: Vec<filter::BoxedFilterPolicy>,
+    pub filter_type: FilterType,
+    /// If not 0, tables get a range filter: a per-block filter over the first
+    /// `range_filter_prefix_len` bytes of every key. It allows skipping blocks (and tables) when
+    /// scanning a range whose bounds share such a prefix, and only works with a bytewise
+    /// comparator.
+    pub range_filter_prefix_len: usize,
+    /// Values larger than this are stored in the blob file of tables built with
+    /// `TableBuilder::new_with_blob_file()`.
+    pub blob_value_threshold: usize,
+    /// If set, a table's index and filter blocks are not held in memory for the lifetime of the
+    /// table, but read through the block cache whenever they are needed.
+    pub cache_index_and_filter_blocks: bool,
+    /// Together with `cache_index_and_filter_blocks`, keeps index and filter blocks in memory once
+    /// they have been read for the first time.
+    pub pin_index_and_filter_blocks: bool,
+    /// If set, tables record the length and checksum of the whole file in their footer, which
+    /// `Table::verify_file_checksum()` checks. Such tables can't be read by LevelDB.
+    pub file_checksum: bool,
+    /// If set, tables contain a properties block with statistics about the table (see
+    /// `TableProperties`), which `Table::properties()` returns and `Table::approx_entries()` uses
+    /// for its estimate. LevelDB ignores the block.
+    pub properties_block: bool,
 }
 
 impl Options {
@@ -50,6 +162,52 @@ impl Options {
         self.block_cache = share(Cache::new(capacity));
         self
     }
+
+    /// Configure for tables with internal keys (see `build_internal_key()`): wraps the comparator
+    /// in an `InternalKeyCmp`, and the filter policies in `InternalFilterPolicy`s so that filters
+    /// are built over user keys. Such tables must be written and read with these options.
+    pub fn with_internal_keys(mut self) -> Options {
+        self.cmp = Arc::new(Box::new(InternalKeyCmp::new(self.cmp)));
+        self.filter_policy = internal_filter_policy(self.filter_policy);
+        self.filter_policies = self
+            .filter_policies
+            .into_iter()
+            .map(internal_filter_policy)
+            .collect();
+        self
+    }
+}
+
+fn internal_filter_policy(policy: filter::BoxedFilterPolicy) -> filter::BoxedFilterPolicy {
+    Arc::new(Box::new(filter::InternalFilterPolicy::new(policy)))
+}
+
+/// ReadOptions control how a single read operation (a lookup or an iterator) accesses a table.
+/// The defaults are defined in the `Default` implementation.
+#[derive(Clone, Copy, Debug)]
+pub struct ReadOptions {
+    /// Whether data blocks read from disk are inserted into the block cache. Blocks already
+    /// present in the cache are used either way. Disable this for large scans that would
+    /// otherwise evict the working set.
+    pub fill_cache: bool,
+    /// Whether the checksums of blocks read from disk are verified.
+    pub verify_checksums: bool,
+    /// Whether iterators skip entries covered by a range deletion (see
+    /// `TableBuilder::delete_range()`). By default, iterators return all entries stored in a
+    /// table, e.g. for a compaction that applies the range deletions itself, as `merge_tables()`
+    /// does. Point lookups (`Table::get_with_options()`, `Table::get_at_with_options()`) ignore
+    /// this flag and always apply range deletions.
+    pub hide_range_deletions: bool,
+}
+
+impl Default for ReadOptions {
+    fn default() -> ReadOptions {
+        ReadOptions {
+            fill_cache: true,
+            verify_checksums: true,
+            hide_range_deletions: false,
+        }
+    }
 }
 
 impl Default for Options {
@@ -62,8 +220,26 @@ impl Default for Options {
             block_size: BLOCK_MAX_SIZE,
             block_restart_interval: 16,
             compression_type: CompressionType::CompressionNone,
+            block_format: BlockFormat::Standard,
+            checksum_type: ChecksumType::Crc32c,
+            index_type: IndexType::SingleLevel,
+            data_block_index_type: DataBlockIndexType::BinarySearch,
+            data_block_hash_ratio: 0.75,
             filter_policy: Arc::new(Box::new(filter::BloomPolicy::new(DEFAULT_BITS_PER_KEY))),
+            filter_policies: vec![
+                Arc::new(Box::new(filter::BloomPolicy::new(DEFAULT_BITS_PER_KEY))),
+                Arc::new(Box::new(filter::BlockedBloomPolicy::new(
+                    DEFAULT_BITS_PER_KEY,
+                ))),
+                Arc::new(Box::new(filter::XorPolicy::new())),
+            ],
+            filter_type: FilterType::PerBlock,
+            range_filter_prefix_len: 0,
+            blob_value_threshold: BLOB_VALUE_THRESHOLD,
+            cache_index_and_filter_blocks: false,
+            pin_index_and_filter_blocks: false,
+            file_checksum: false,
+            properties_block: false,
         }
     }
 }
-
@@ -0,0 +1,132 @@
+//! The properties block is a meta block containing statistics about a table. Its metaindex key is
+//! PROPERTIES_BLOCK_KEY; its entries map property names to varint-encoded values. Readers ignore
+//! properties they don't know, and LevelDB ignores the whole block.
+
+use crate::block::{Block, BlockContents};
+use crate::block_builder::BlockBuilder;
+use crate::cmp::DefaultCmp;
+use crate::options::Options;
+use crate::types::SSIterator;
+
+use std::sync::Arc;
+
+use integer_encoding::VarInt;
+
+pub const PROPERTIES_BLOCK_KEY: &[u8] = b"sstable.properties";
+
+const DATA_SIZE: &[u8] = b"sstable.data.size";
+const NUM_DATA_BLOCKS: &[u8] = b"sstable.num.data.blocks";
+const NUM_ENTRIES: &[u8] = b"sstable.num.entries";
+const RAW_KEY_SIZE: &[u8] = b"sstable.raw.key.size";
+const RAW_VALUE_SIZE: &[u8] = b"sstable.raw.value.size";
+
+/// TableProperties contains statistics about a table, which are recorded by `TableBuilder`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TableProperties {
+    pub num_entries: usize,
+    pub num_data_blocks: usize,
+    /// Total on-disk size of the data blocks, including their trailers.
+    pub data_size: usize,
+    /// Total size of the keys as added to the table.
+    pub raw_key_size: usize,
+    /// Total size of the values as added to the table.
+    pub raw_value_size: usize,
+}
+
+/// Property names are always ordered bytewise, independent of the table's comparator.
+pub(crate) fn block_options(opt: &Options) -> Options {
+    Options {
+        cmp: Arc::new(Box::new(DefaultCmp)),
+        ..opt.clone()
+    }
+}
+
+impl TableProperties {
+    pub fn encode(&self, opt: &Options) -> BlockContents {
+        let mut builder = BlockBuilder::new(block_options(opt));
+        // In the order of the property names.
+        for (name, val) in [
+            (DATA_SIZE, self.data_size),
+            (NUM_DATA_BLOCKS, self.num_data_blocks),
+            (NUM_ENTRIES, self.num_entries),
+            (RAW_KEY_SIZE, self.raw_key_size),
+            (RAW_VALUE_SIZE, self.raw_value_size),
+        ] {
+            builder.add(name, &val.encode_var_vec());
+        }
+        builder.finish()
+    }
+
+    /// Returns an upper bound for the size of any encoded properties block.
+    pub fn max_encoded_size(opt: &Options) -> usize {
+        TableProperties {
+            num_entries: usize::MAX,
+            num_data_blocks: usize::MAX,
+            data_size: usize::MAX,
+            raw_key_size: usize::MAX,
+            raw_value_size: usize::MAX,
+        }
+        .encode(opt)
+        .len()
+    }
+
+    /// Decodes a properties block; `contents` must be read with the table's block format.
+    pub fn decode(opt: &Options, contents: BlockContents) -> TableProperties {
+        let mut props = TableProperties::default();
+        let block = Block::new(block_options(opt), contents);
+        let mut iter = block.iter();
+        while let Some((name, val)) = SSIterator::next(&mut iter) {
+            let val = match usize::decode_var(&val) {
+                Some((v, _)) => v,
+                None => continue,
+            };
+            match name.as_slice() {
+                DATA_SIZE => props.data_size = val,
+                NUM_DATA_BLOCKS => props.num_data_blocks = val,
+                NUM_ENTRIES => props.num_entries = val,
+                RAW_KEY_SIZE => props.raw_key_size = val,
+                RAW_VALUE_SIZE => props.raw_value_size = val,
+                _ => {}
+            }
+        }
+        props
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::BlockFormat;
+
+    #[test]
+    fn test_properties_roundtrip() {
+        let props = TableProperties {
+            num_entries: 1000,
+            num_data_blocks: 12,
+            data_size: 45678,
+            raw_key_size: 9000,
+            raw_value_size: 30000,
+        };
+        for format in [BlockFormat::Standard, BlockFormat::Compact] {
+            let opt = Options {
+                block_format: format,
+                ..Options::default()
+            };
+            assert_eq!(TableProperties::decode(&opt, props.encode(&opt)), props);
+            assert!(props.encode(&opt).len() < TableProperties::max_encoded_size(&opt));
+
+            let max = TableProperties {
+                num_entries: usize::MAX,
+                num_data_blocks: usize::MAX,
+                data_size: usize::MAX,
+                raw_key_size: usize::MAX,
+                raw_value_size: usize::MAX,
+            };
+            assert_eq!(TableProperties::decode(&opt, max.encode(&opt)), max);
+            assert_eq!(
+                max.encode(&opt).len(),
+                TableProperties::max_encoded_size(&opt)
+            );
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! The range deletions block is a meta block containing the key ranges deleted by
+//! `TableBuilder::delete_range()`. Its metaindex key is RANGE_DELETIONS_BLOCK_KEY; its entries map
+//! the start of each range to its (exclusive) end. Ranges are merged before they are written, so
+//! they don't overlap and are ordered by the table's comparator.
+//!
+//! In tables with internal keys, a range deletion has a sequence number, and deletes the versions
+//! of the user keys in the range with a smaller sequence number (see
+//! `TableBuilder::delete_range_at()`). Both bounds are internal keys: the start carries the
+//! sequence number of the deletion, and the end is the first internal key of the end's user key.
+//! Such ranges are only merged if they have the same start, and may overlap otherwise.
+
+use crate::block::{Block, BlockContents};
+use crate::block_builder::BlockBuilder;
+use crate::cmp::{self, Cmp};
+use crate::key_types::{self, SequenceNumber, ValueType, MAX_SEQUENCE_NUMBER};
+use crate::options::Options;
+use crate::types::SSIterator;
+
+use std::cmp::Ordering;
+
+pub const RANGE_DELETIONS_BLOCK_KEY: &[u8] = b"sstable.range_deletions";
+
+/// Returns an upper bound for the size of the block encoding `ranges` (after merging).
+pub fn max_encoded_size(ranges: &[(Vec<u8>, Vec<u8>)]) -> usize {
+    ranges
+        .iter()
+        // Three varints and (at most) a restart point per entry.
+        .map(|(start, end)| start.len() + end.len() + 3 * 5 + 4)
+        .sum::<usize>()
+        + 8
+}
+
+/// Returns the bounds of a range deletion of the user keys in [start, end) at sequence number
+/// `seq`, in a table with internal keys.
+pub fn internal_range(start: &[u8], end: &[u8], seq: SequenceNumber) -> (Vec<u8>, Vec<u8>) {
+    (
+        key_types::build_internal_key(start, seq, ValueType::TypeDeletion),
+        key_types::build_internal_key(end, MAX_SEQUENCE_NUMBER, ValueType::TypeValue),
+    )
+}
+
+fn range_seq(start: &[u8]) -> SequenceNumber {
+    key_types::split_internal_key(start).1 >> 8
+}
+
+/// RangeDeletions is a set of key ranges [start, end). Without internal keys, the ranges don't
+/// overlap.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RangeDeletions {
+    ranges: Vec<(Vec<u8>, Vec<u8>)>,
+    // Whether the bounds are internal keys (see the module documentation).
+    internal_keys: bool,
+}
+
+impl RangeDeletions {
+    /// Creates a set from possibly overlapping ranges; empty ranges are dropped. With an
+    /// InternalKeyCmp, the bounds must be built by `internal_range()`.
+    pub fn new(cmp: &dyn Cmp, mut ranges: Vec<(Vec<u8>, Vec<u8>)>) -> RangeDeletions {
+        let internal_keys = cmp::is_internal_key_cmp(cmp);
+        ranges.retain(|(start, end)| cmp.cmp(start, end) == Ordering::Less);
+        ranges.sort_by(|a, b| cmp.cmp(&a.0, &b.0));
+
+        let mut merged: Vec<(Vec<u8>, Vec<u8>)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            if let Some(last) = merged.last_mut() {
+                // Adjacent ranges are merged, too. Ranges with internal keys only if they are
+                // deletions of the same sequence number starting at the same key.
+                let merge = if internal_keys {
+                    cmp.cmp(&start, &last.0) == Ordering::Equal
+                } else {
+                    cmp.cmp(&start, &last.1) != Ordering::Greater
+                };
+                if merge {
+                    if cmp.cmp(&end, &last.1) == Ordering::Greater {
+                        last.1 = end;
+                    }
+                    continue;
+                }
+            }
+            merged.push((start, end));
+        }
+        RangeDeletions {
+            ranges: merged,
+            internal_keys,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Returns true if the bounds are internal keys.
+    pub fn has_internal_keys(&self) -> bool {
+        self.internal_keys
+    }
+
+    pub fn ranges(&self) -> &[(Vec<u8>, Vec<u8>)] {
+        &self.ranges
+    }
+
+    /// Returns a range deleting `key`, if there is one. With internal keys, `key` is an internal
+    /// key, i.e. a version of a user key, and ranges are checked one by one.
+    pub fn covering(&self, cmp: &dyn Cmp, key: &[u8]) -> Option<&(Vec<u8>, Vec<u8>)> {
+        let ix = self
+            .ranges
+            .partition_point(|(start, _)| cmp.cmp(start, key) != Ordering::Greater);
+        if self.internal_keys {
+            let seq = key_types::split_internal_key(key).1 >> 8;
+            return self.ranges[..ix].iter().find(|(start, end)| {
+                seq < range_seq(start) && cmp.cmp(key, end) == Ordering::Less
+            });
+        }
+        match ix.checked_sub(1).map(|ix| &self.ranges[ix]) {
+            Some(range) if cmp.cmp(key, &range.1) == Ordering::Less => Some(range),
+            _ => None,
+        }
+    }
+
+    /// With internal keys, returns the highest sequence number of the ranges containing
+    /// `user_key` that is not greater than `seq`. Ranges deleted at MAX_SEQUENCE_NUMBER (by
+    /// `TableBuilder::delete_range()`) are visible at every sequence number.
+    pub fn newest_covering(
+        &self,
+        cmp: &dyn Cmp,
+        user_key: &[u8],
+        seq: SequenceNumber,
+    ) -> Option<SequenceNumber> {
+        if !self.internal_keys {
+            return None;
+        }
+        // The last internal key of `user_key`, which is contained in all ranges containing
+        // `user_key`.
+        let key = key_types::build_internal_key(user_key, 0, ValueType::TypeDeletion);
+        let ix = self
+            .ranges
+            .partition_point(|(start, _)| cmp.cmp(start, &key) != Ordering::Greater);
+        self.ranges[..ix]
+            .iter()
+            .filter(|(start, end)| {
+                let range_seq = range_seq(start);
+                (range_seq <= seq || range_seq == MAX_SEQUENCE_NUMBER)
+                    && cmp.cmp(&key, end) == Ordering::Less
+            })
+            .map(|(start, _)| range_seq(start))
+            .max()
+    }
+
+    pub fn encode(&self, opt: &Options) -> BlockContents {
+        let mut builder = BlockBuilder::new(opt.clone());
+        for (start, end) in self.ranges.iter() {
+            builder.add(start, end);
+        }
+        builder.finish()
+    }
+
+    /// Decodes a range deletions block; `contents` must be read with the table's block format.
+    pub fn decode(opt: &Options, contents: BlockContents) -> RangeDeletions {
+        let block = Block::new(opt.clone(), contents);
+        let mut iter = block.iter();
+        let mut ranges = vec![];
+        while let Some(range) = SSIterator::next(&mut iter) {
+            ranges.push(range);
+        }
+        RangeDeletions {
+            ranges,
+            internal_keys: cmp::is_internal_key_cmp(opt.cmp.as_ref().as_ref()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmp::DefaultCmp;
+
+    fn range(start: &str, end: &str) -> (Vec<u8>, Vec<u8>) {
+        (start.as_bytes().to_vec(), end.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_range_deletions() {
+        let dels = RangeDeletions::new(
+            &DefaultCmp,
+            vec![
+                range("m", "p"),
+                range("c", "e"),
+                range("a", "c"),
+                range("x", "x"),
+                range("n", "o"),
+                range("f", "h"),
+                range("g", "j"),
+            ],
+        );
+        assert_eq!(
+            dels.ranges(),
+            &[range("a", "e"), range("f", "j"), range("m", "p")]
+        );
+
+        for (key, covering) in [
+            ("", None),
+            ("a", Some(range("a", "e"))),
+            ("d\x7f", Some(range("a", "e"))),
+            ("e", None),
+            ("i", Some(range("f", "j"))),
+            ("j", None),
+            ("o", Some(range("m", "p"))),
+            ("z", None),
+        ] {
+            assert_eq!(
+                dels.covering(&DefaultCmp, key.as_bytes()),
+                covering.as_ref()
+            );
+        }
+
+        let opt = Options::default();
+        let encoded = dels.encode(&opt);
+        assert!(encoded.len() <= max_encoded_size(dels.ranges()));
+        assert_eq!(RangeDeletions::decode(&opt, encoded), dels);
+        assert!(RangeDeletions::new(&DefaultCmp, vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_range_deletions_internal_keys() {
+        let opt = Options::default().with_internal_keys();
+        let cmp = opt.cmp.as_ref().as_ref();
+        let dels = RangeDeletions::new(
+            cmp,
+            vec![
+                internal_range(b"key-0005", b"key-0009", 20),
+                internal_range(b"key-0001", b"key-0004", 10),
+                internal_range(b"key-0001", b"key-0006", 10),
+                internal_range(b"key-0003", b"key-0003", 30),
+                internal_range(b"key-0008", b"key-0010", MAX_SEQUENCE_NUMBER),
+            ],
+        );
+        assert!(dels.has_internal_keys());
+        assert_eq!(
+            dels.ranges(),
+            &[
+                internal_range(b"key-0001", b"key-0006", 10),
+                internal_range(b"key-0005", b"key-0009", 20),
+                internal_range(b"key-0008", b"key-0010", MAX_SEQUENCE_NUMBER),
+            ]
+        );
+
+        let version =
+            |key: &[u8], seq| key_types::build_internal_key(key, seq, ValueType::TypeValue);
+        for (key, seq, covering) in [
+            (&b"key-0000"[..], 1, None),
+            (b"key-0001", 9, Some(0)),
+            (b"key-0001", 10, None),
+            (b"key-0005", 15, Some(1)),
+            (b"key-0005", 5, Some(0)),
+            (b"key-0006", 5, Some(1)),
+            (b"key-0009", 500, Some(2)),
+            (b"key-0010", 5, None),
+        ] {
+            assert_eq!(
+                dels.covering(cmp, &version(key, seq)),
+                covering.map(|ix| &dels.ranges()[ix])
+            );
+        }
+
+        for (key, seq, newest) in [
+            (&b"key-0000"[..], 100, None),
+            (b"key-0005", 9, None),
+            (b"key-0005", 15, Some(10)),
+            (b"key-0005", 25, Some(20)),
+            (b"key-0008", 25, Some(MAX_SEQUENCE_NUMBER)),
+            (b"key-0008", 0, Some(MAX_SEQUENCE_NUMBER)),
+        ] {
+            assert_eq!(dels.newest_covering(cmp, key, seq), newest);
+        }
+
+        let encoded = dels.encode(&opt);
+        assert!(encoded.len() <= max_encoded_size(dels.ranges()));
+        assert_eq!(RangeDeletions::decode(&opt, encoded), dels);
+    }
+}
@@ -0,0 +1,435 @@
+//! Reading tables written by RocksDB's block-based table format (format versions 2 to 5).
+//!
+//! RocksDB tables are recognised by their footer (see `table_builder::Footer`). Their data blocks
+//! have the same format as the ones of this crate, and their keys are internal keys encoded like
+//! ours (see the `key_types` module): such tables are read with an `InternalKeyCmp`, and keys are
+//! looked up with `Table::get_at()`. Only tables with the bytewise comparator, Snappy or no
+//! compression and a binary search index can be read; RocksDB's filters are ignored.
+//!
+//! Besides values and deletions, RocksDB keys may have other value types: single deletions are
+//! read as deletions, but looking up a key whose newest visible version is a merge operand or a
+//! blob index returns a NotSupported error. So does `Table::get()` for such an entry, and
+//! iterators stop at it with that error in `status()`. The range deletions of the `rocksdb.range_del` meta
+//! block are converted to the ones of this crate (see `convert_range_deletions()`).
+//!
+//! The properties block of RocksDB tables has its own property names, and their index block may
+//! contain user keys instead of internal keys, and delta-encoded block handles. The index block is
+//! converted to the format of this crate when a table is opened (see `convert_index_block()`).
+
+use crate::block::{Block, BlockContents};
+use crate::block_builder::BlockBuilder;
+use crate::blockhandle::BlockHandle;
+use crate::cmp::{Cmp, DefaultCmp};
+use crate::error::{err, Result, StatusCode};
+use crate::key_types::{self, SequenceNumber, ValueType};
+use crate::options::Options;
+use crate::properties::{self, TableProperties};
+use crate::range_deletions::{self, RangeDeletions};
+use crate::table_builder::{TABLE_BLOCK_CKSUM_LEN, TABLE_BLOCK_COMPRESS_LEN};
+use crate::types::SSIterator;
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+use integer_encoding::{FixedInt, VarInt};
+
+pub const ROCKSDB_PROPERTIES_BLOCK_KEY: &[u8] = b"rocksdb.properties";
+pub const ROCKSDB_RANGE_DELETIONS_BLOCK_KEY: &[u8] = b"rocksdb.range_del";
+
+/// Value types of RocksDB internal keys that don't exist in this crate. Deletions and values have
+/// the same types as ours.
+const TYPE_MERGE: u64 = 0x2;
+const TYPE_SINGLE_DELETION: u64 = 0x7;
+const TYPE_RANGE_DELETION: u64 = 0xf;
+const TYPE_BLOB_INDEX: u64 = 0x11;
+
+const COMPARATOR: &[u8] = b"rocksdb.comparator";
+const COMPRESSION: &[u8] = b"rocksdb.compression";
+const DATA_SIZE: &[u8] = b"rocksdb.data.size";
+const INDEX_KEY_IS_USER_KEY: &[u8] = b"rocksdb.index.key.is.user.key";
+const INDEX_TYPE: &[u8] = b"rocksdb.block.based.table.index.type";
+const INDEX_VALUE_IS_DELTA_ENCODED: &[u8] = b"rocksdb.index.value.is.delta.encoded";
+const NUM_DATA_BLOCKS: &[u8] = b"rocksdb.num.data.blocks";
+const NUM_ENTRIES: &[u8] = b"rocksdb.num.entries";
+const RAW_KEY_SIZE: &[u8] = b"rocksdb.raw.key.size";
+const RAW_VALUE_SIZE: &[u8] = b"rocksdb.raw.value.size";
+
+/// The compression types (as named in the properties block) of tables that can be read.
+const SUPPORTED_COMPRESSIONS: [&[u8]; 2] = [b"NoCompression", b"Snappy"];
+
+/// A binary search index; a hash search index is one, too, with additional meta blocks for prefix
+/// seeks. Partitioned indexes and indexes containing the first key of each block can't be read.
+const INDEX_TYPE_BINARY_SEARCH: u32 = 0;
+const INDEX_TYPE_HASH_SEARCH: u32 = 1;
+
+/// RocksDbProperties contains the properties of a RocksDB table that are needed to read it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RocksDbProperties {
+    pub table: TableProperties,
+    /// The index keys are user keys (without tag).
+    pub index_key_is_user_key: bool,
+    /// The index values are delta-encoded block handles.
+    pub index_value_is_delta_encoded: bool,
+}
+
+impl RocksDbProperties {
+    /// Decodes a RocksDB properties block. Returns a NotSupported error for tables that can't be
+    /// read, e.g. because of their comparator.
+    pub fn decode(opt: &Options, contents: BlockContents) -> Result<RocksDbProperties> {
+        let mut props = RocksDbProperties::default();
+        let block = Block::new(properties::block_options(opt), contents);
+        let mut iter = block.iter();
+        while let Some((name, val)) = SSIterator::next(&mut iter) {
+            let varint = || usize::decode_var(&val).map(|(v, _)| v).unwrap_or(0);
+            match name.as_slice() {
+                COMPARATOR if val != DefaultCmp.id().as_bytes() => {
+                    return err(
+                        StatusCode::NotSupported,
+                        &format!(
+                            "unsupported RocksDB comparator {}",
+                            String::from_utf8_lossy(&val)
+                        ),
+                    );
+                }
+                COMPRESSION if !SUPPORTED_COMPRESSIONS.contains(&val.as_slice()) => {
+                    return err(
+                        StatusCode::NotSupported,
+                        &format!(
+                            "unsupported RocksDB compression {}",
+                            String::from_utf8_lossy(&val)
+                        ),
+                    );
+                }
+                INDEX_TYPE => {
+                    let index_type = if val.len() == 4 {
+                        Some(u32::decode_fixed(&val))
+                    } else {
+                        None
+                    };
+                    if index_type != Some(INDEX_TYPE_BINARY_SEARCH)
+                        && index_type != Some(INDEX_TYPE_HASH_SEARCH)
+                    {
+                        return err(
+                            StatusCode::NotSupported,
+                            &format!("unsupported RocksDB index type {:?}", index_type),
+                        );
+                    }
+                }
+                DATA_SIZE => props.table.data_size = varint(),
+                NUM_DATA_BLOCKS => props.table.num_data_blocks = varint(),
+                NUM_ENTRIES => props.table.num_entries = varint(),
+                RAW_KEY_SIZE => props.table.raw_key_size = varint(),
+                RAW_VALUE_SIZE => props.table.raw_value_size = varint(),
+                INDEX_KEY_IS_USER_KEY => props.index_key_is_user_key = varint() != 0,
+                INDEX_VALUE_IS_DELTA_ENCODED => props.index_value_is_delta_encoded = varint() != 0,
+                _ => {}
+            }
+        }
+        Ok(props)
+    }
+}
+
+/// Splits a RocksDB internal key into user key, sequence number and value type. Single deletions
+/// are deletions; merge operands and blob indexes can't be read.
+pub fn parse_internal_key(key: &[u8]) -> Result<(&[u8], SequenceNumber, ValueType)> {
+    if let Some(parsed) = key_types::parse_internal_key(key) {
+        return Ok(parsed);
+    }
+    let (user_key, tag) = key_types::split_internal_key(key);
+    if user_key.len() == key.len() {
+        return err(StatusCode::Corruption, "invalid internal key in table");
+    }
+    match tag & 0xff {
+        TYPE_SINGLE_DELETION => Ok((user_key, tag >> 8, ValueType::TypeDeletion)),
+        TYPE_MERGE => err(
+            StatusCode::NotSupported,
+            "RocksDB merge operands are not supported",
+        ),
+        TYPE_BLOB_INDEX => err(
+            StatusCode::NotSupported,
+            "RocksDB blob indexes are not supported",
+        ),
+        _ => err(StatusCode::Corruption, "invalid internal key in table"),
+    }
+}
+
+/// Converts the contents of a RocksDB range deletions block into the range deletions of a table
+/// read with `opt` (whose comparator must be an `InternalKeyCmp`). Its keys are the internal keys
+/// of the range starts, with the sequence number of the deletion, and its values the user keys of
+/// the (exclusive) range ends.
+pub fn convert_range_deletions(opt: &Options, contents: BlockContents) -> Result<RangeDeletions> {
+    let block = Block::new(opt.clone(), contents);
+    let mut iter = block.iter();
+    let mut ranges = vec![];
+    while let Some((start, end)) = SSIterator::next(&mut iter) {
+        let (start, tag) = key_types::split_internal_key(&start);
+        if tag & 0xff != TYPE_RANGE_DELETION {
+            return err(
+                StatusCode::Corruption,
+                "invalid key in RocksDB range deletions block",
+            );
+        }
+        ranges.push(range_deletions::internal_range(start, &end, tag >> 8));
+    }
+    Ok(RangeDeletions::new(opt.cmp.as_ref().as_ref(), ranges))
+}
+
+/// Decodes the varint at `off` in `src`, and advances `off` past it.
+fn decode_var<V: VarInt>(src: &[u8], off: &mut usize) -> Result<V> {
+    match src.get(*off..).and_then(V::decode_var) {
+        Some((v, len)) => {
+            *off += len;
+            Ok(v)
+        }
+        None => err(StatusCode::Corruption, "bad varint in RocksDB index block"),
+    }
+}
+
+/// Converts the contents of a RocksDB index block into an index block of this crate, built with
+/// `opt` (whose comparator must be an `InternalKeyCmp`).
+///
+/// A RocksDB index block has the same layout as other blocks, but if its values are
+/// delta-encoded, entries have no VALSIZE, and the VALUE of entries sharing a key prefix with the
+/// previous entry is a signed varint: the difference of the block size to the previous one. The
+/// block offset is implied, as data blocks follow each other.
+pub fn convert_index_block(
+    opt: &Options,
+    contents: &[u8],
+    props: &RocksDbProperties,
+) -> Result<BlockContents> {
+    let corrupt = |what: &str| {
+        err(
+            StatusCode::Corruption,
+            &format!("{} in RocksDB index block", what),
+        )
+    };
+    if contents.len() < 4 {
+        return corrupt("no restarts");
+    }
+    let num_restarts = u32::decode_fixed(&contents[contents.len() - 4..]) as usize;
+    let entries = match (contents.len() - 4).checked_sub(4 * num_restarts) {
+        Some(end) => &contents[..end],
+        None => return corrupt("bad number of restarts"),
+    };
+
+    let mut builder = BlockBuilder::new(opt.clone());
+    let (mut key, mut last_key) = (vec![], vec![]);
+    let mut prev: Option<BlockHandle> = None;
+    let mut off = 0;
+    while off < entries.len() {
+        let shared: usize = decode_var(entries, &mut off)?;
+        let non_shared: usize = decode_var(entries, &mut off)?;
+        let value_len: Option<usize> = if props.index_value_is_delta_encoded {
+            None
+        } else {
+            Some(decode_var(entries, &mut off)?)
+        };
+        if shared > key.len() || non_shared > entries.len() - off {
+            return corrupt("bad key");
+        }
+        key.truncate(shared);
+        key.extend_from_slice(&entries[off..off + non_shared]);
+        off += non_shared;
+
+        let handle = match (value_len, prev.as_ref()) {
+            (Some(len), _) => {
+                if len > entries.len() - off {
+                    return corrupt("bad value");
+                }
+                let value = &entries[off..off + len];
+                off += len;
+                let mut value_off = 0;
+                let offset = decode_var(value, &mut value_off)?;
+                BlockHandle::new(offset, decode_var(value, &mut value_off)?)
+            }
+            (None, Some(prev)) if shared > 0 => {
+                let delta: i64 = decode_var(entries, &mut off)?;
+                let size = match usize::try_from(prev.size() as i64 + delta) {
+                    Ok(size) => size,
+                    Err(_) => return corrupt("bad block size"),
+                };
+                BlockHandle::new(
+                    prev.offset() + prev.size() + TABLE_BLOCK_COMPRESS_LEN + TABLE_BLOCK_CKSUM_LEN,
+                    size,
+                )
+            }
+            (None, _) => {
+                let offset = decode_var(entries, &mut off)?;
+                BlockHandle::new(offset, decode_var(entries, &mut off)?)
+            }
+        };
+
+        let index_key = if props.index_key_is_user_key {
+            // The last internal key with this user key, which is at least the last key in the
+            // block.
+            key_types::build_internal_key(&key, 0, ValueType::TypeDeletion)
+        } else {
+            key.clone()
+        };
+        if prev.is_some() && opt.cmp.cmp(&last_key, &index_key) != Ordering::Less {
+            return corrupt("keys out of order");
+        }
+        let mut value = [0; 20];
+        let value_len = handle.encode_to(&mut value);
+        builder.add(&index_key, &value[..value_len]);
+        last_key = index_key;
+        prev = Some(handle);
+    }
+    Ok(builder.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::test_util::encode_rocksdb_index_block;
+    use crate::types::current_key_val;
+
+    fn internal_key(key: &str) -> Vec<u8> {
+        key_types::build_internal_key(key.as_bytes(), 7, ValueType::TypeValue)
+    }
+
+    #[test]
+    fn test_rocksdb_convert_index_block() {
+        let opt = Options::default().with_internal_keys();
+        let handles = [
+            BlockHandle::new(0, 100),
+            BlockHandle::new(105, 98),
+            BlockHandle::new(208, 130),
+            BlockHandle::new(343, 7),
+        ];
+        let keys = ["abc", "abd", "b", "bcd"];
+
+        for (user_keys, delta) in [(false, false), (true, false), (false, true), (true, true)] {
+            let props = RocksDbProperties {
+                index_key_is_user_key: user_keys,
+                index_value_is_delta_encoded: delta,
+                ..RocksDbProperties::default()
+            };
+            let entries: Vec<_> = keys
+                .iter()
+                .zip(handles.iter())
+                .map(|(k, h)| {
+                    let k = if user_keys {
+                        k.as_bytes().to_vec()
+                    } else {
+                        internal_key(k)
+                    };
+                    (k, h.clone())
+                })
+                .collect();
+            let contents = encode_rocksdb_index_block(&entries, 2, delta);
+            let block = Block::new(
+                opt.clone(),
+                convert_index_block(&opt, &contents, &props).unwrap(),
+            );
+
+            let mut iter = block.iter();
+            for (key, handle) in keys.iter().zip(handles.iter()) {
+                let (k, v) = SSIterator::next(&mut iter).unwrap();
+                let (user_key, tag) = key_types::split_internal_key(&k);
+                assert_eq!(user_key, key.as_bytes());
+                assert_eq!(tag, if user_keys { 0 } else { 7 << 8 | 1 });
+                let (h, _) = BlockHandle::decode(&v);
+                assert_eq!((h.offset(), h.size()), (handle.offset(), handle.size()));
+            }
+            assert!(SSIterator::next(&mut iter).is_none());
+
+            // Lookups of newer versions of a key find its block.
+            iter.seek(&key_types::build_internal_key(
+                b"abd",
+                9,
+                ValueType::TypeValue,
+            ));
+            let (_, v) = current_key_val(&iter).unwrap();
+            assert_eq!(BlockHandle::decode(&v).0.offset(), 105);
+
+            let truncated = &contents[..contents.len() / 2];
+            assert!(convert_index_block(&opt, truncated, &props).is_err());
+        }
+    }
+
+    #[test]
+    fn test_rocksdb_properties() {
+        let opt = Options::default();
+        let encode = |props: &[(&[u8], &[u8])]| {
+            let mut builder = BlockBuilder::new(properties::block_options(&opt));
+            for (name, val) in props {
+                builder.add(name, val);
+            }
+            builder.finish()
+        };
+
+        let props = RocksDbProperties::decode(
+            &opt,
+            encode(&[
+                (INDEX_TYPE, &0u32.encode_fixed_vec()),
+                (COMPARATOR, b"leveldb.BytewiseComparator"),
+                (COMPRESSION, b"Snappy"),
+                (DATA_SIZE, &4000usize.encode_var_vec()),
+                (INDEX_KEY_IS_USER_KEY, &1usize.encode_var_vec()),
+                (INDEX_VALUE_IS_DELTA_ENCODED, &1usize.encode_var_vec()),
+                (NUM_DATA_BLOCKS, &2usize.encode_var_vec()),
+                (NUM_ENTRIES, &150usize.encode_var_vec()),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            props,
+            RocksDbProperties {
+                table: TableProperties {
+                    num_entries: 150,
+                    num_data_blocks: 2,
+                    data_size: 4000,
+                    ..TableProperties::default()
+                },
+                index_key_is_user_key: true,
+                index_value_is_delta_encoded: true,
+            }
+        );
+
+        for (name, val) in [
+            (COMPARATOR, &b"rocksdb.ReverseBytewiseComparator"[..]),
+            (COMPRESSION, b"LZ4"),
+            (INDEX_TYPE, &2u32.encode_fixed_vec()),
+        ] {
+            assert_eq!(
+                RocksDbProperties::decode(&opt, encode(&[(name, val)]))
+                    .unwrap_err()
+                    .code,
+                StatusCode::NotSupported
+            );
+        }
+    }
+
+    #[test]
+    fn test_rocksdb_parse_internal_key() {
+        let key = |t: u64| {
+            let mut key = b"abc".to_vec();
+            key.extend_from_slice(&(9 << 8 | t).encode_fixed_vec());
+            key
+        };
+        assert_eq!(
+            parse_internal_key(&key(1)),
+            Ok((&b"abc"[..], 9, ValueType::TypeValue))
+        );
+        for t in [0, TYPE_SINGLE_DELETION] {
+            assert_eq!(
+                parse_internal_key(&key(t)),
+                Ok((&b"abc"[..], 9, ValueType::TypeDeletion))
+            );
+        }
+        for (t, code) in [
+            (TYPE_MERGE, StatusCode::NotSupported),
+            (TYPE_BLOB_INDEX, StatusCode::NotSupported),
+            (TYPE_RANGE_DELETION, StatusCode::Corruption),
+            (0x42, StatusCode::Corruption),
+        ] {
+            assert_eq!(parse_internal_key(&key(t)).unwrap_err().code, code);
+        }
+        assert_eq!(
+            parse_internal_key(b"abc").unwrap_err().code,
+            StatusCode::Corruption
+        );
+    }
+}
@@ -0,0 +1,298 @@
+use crate::cmp::Cmp;
+use crate::error::{err, Result, StatusCode};
+use crate::merging_iter::MergingIter;
+use crate::options::{Options, ReadOptions};
+use crate::table_builder::TableBuilder;
+use crate::table_reader::Table;
+use crate::types::SSIterator;
+
+use std::cmp::Ordering;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Arc;
+
+/// DuplicateKeyPolicy determines how a SortingTableBuilder handles entries with equal keys.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DuplicateKeyPolicy {
+    /// Only the entry added first is written to the table.
+    KeepFirst,
+    /// Only the entry added last is written to the table.
+    KeepLast,
+    /// `finish()` fails with `StatusCode::AlreadyExists`.
+    Error,
+}
+
+// Makes the names of temporary files unique within the process.
+static RUN_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// SortingTableBuilder builds a table from entries added in any order. Entries are buffered in
+/// memory up to `Options::write_buffer_size` bytes; then they are sorted and written to a
+/// temporary table (a "run") in the temporary directory. `finish()` merges all runs into the
+/// final table.
+pub struct SortingTableBuilder<Dst: Write> {
+    opt: Options,
+    dst: Dst,
+    policy: DuplicateKeyPolicy,
+    tmp_dir: PathBuf,
+
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    buffered: usize,
+    runs: Vec<PathBuf>,
+}
+
+impl<Dst: Write> SortingTableBuilder<Dst> {
+    /// Creates a new sorting table builder, which keeps the last entry of each key and stores
+    /// runs in the system's temporary directory.
+    pub fn new(opt: Options, dst: Dst) -> SortingTableBuilder<Dst> {
+        SortingTableBuilder {
+            opt,
+            dst,
+            policy: DuplicateKeyPolicy::KeepLast,
+            tmp_dir: std::env::temp_dir(),
+            entries: vec![],
+            buffered: 0,
+            runs: vec![],
+        }
+    }
+
+    pub fn with_duplicate_key_policy(mut self, policy: DuplicateKeyPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Configures the directory in which runs are stored.
+    pub fn with_tmp_dir(mut self, dir: &Path) -> Self {
+        self.tmp_dir = dir.to_path_buf();
+        self
+    }
+
+    /// Add an entry to the table. Keys may be added in any order.
+    pub fn add(&mut self, key: &[u8], val: &[u8]) -> Result<()> {
+        self.buffered += key.len() + val.len();
+        self.entries.push((key.to_vec(), val.to_vec()));
+        if self.buffered >= self.opt.write_buffer_size {
+            self.write_run()?;
+        }
+        Ok(())
+    }
+
+    /// Sorts the buffered entries and writes them to a new run.
+    fn write_run(&mut self) -> Result<()> {
+        let path = self.tmp_dir.join(format!(
+            "sstable-sort-{}-{}.tmp",
+            std::process::id(),
+            RUN_COUNTER.fetch_add(1, AtomicOrdering::Relaxed)
+        ));
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        // Remove the run if writing it fails.
+        self.runs.push(path);
+
+        let mut run = TableBuilder::new_no_filter(self.run_options(), BufWriter::new(file));
+        let mut dedup = Dedup::new(self.policy, self.opt.cmp.clone());
+        for (key, val) in self.sorted_entries() {
+            dedup.add(key, val, &mut run)?;
+        }
+        dedup.finish(&mut run)?;
+        run.finish()?;
+
+        self.buffered = 0;
+        Ok(())
+    }
+
+    /// Takes the buffered entries and sorts them. Entries with equal keys stay in the order
+    /// they were added.
+    fn sorted_entries(&mut self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut entries = std::mem::take(&mut self.entries);
+        let cmp = self.opt.cmp.clone();
+        entries.sort_by(|a, b| cmp.cmp(&a.0, &b.0));
+        entries
+    }
+
+    fn run_options(&self) -> Options {
+        let mut opt = self.opt.clone();
+        opt.range_filter_prefix_len = 0;
+        opt
+    }
+
+    /// Writes all entries to the table and finishes it. Returns the size of the table, like
+    /// `TableBuilder::finish()`.
+    pub fn finish(mut self) -> Result<usize> {
+        let opt = self.opt.clone();
+        let policy = self.policy;
+
+        // Everything fits into memory: no runs are needed.
+        if self.runs.is_empty() {
+            let entries = self.sorted_entries();
+            let mut dedup = Dedup::new(policy, opt.cmp.clone());
+            let mut builder = TableBuilder::new(opt, &mut self.dst);
+            for (key, val) in entries {
+                dedup.add(key, val, &mut builder)?;
+            }
+            dedup.finish(&mut builder)?;
+            return builder.finish();
+        }
+
+        if !self.entries.is_empty() {
+            self.write_run()?;
+        }
+
+        // The runs are merged in the order they were written, so that the order of entries with
+        // equal keys is retained.
+        let ropt = ReadOptions {
+            fill_cache: false,
+            ..ReadOptions::default()
+        };
+        let mut iters: Vec<Box<dyn SSIterator>> = Vec::with_capacity(self.runs.len());
+        for path in self.runs.iter() {
+            let table = Table::new_from_file(self.run_options(), path)?;
+            iters.push(Box::new(table.iter_with_options(&ropt)));
+        }
+        let mut merged = MergingIter::new(opt.cmp.clone(), iters);
+
+        let mut dedup = Dedup::new(policy, opt.cmp.clone());
+        let mut builder = TableBuilder::new(opt, &mut self.dst);
+        while let Some((key, val)) = SSIterator::next(&mut merged) {
+            dedup.add(key, val, &mut builder)?;
+        }
+        dedup.finish(&mut builder)?;
+        builder.finish()
+    }
+}
+
+impl<Dst: Write> Drop for SortingTableBuilder<Dst> {
+    fn drop(&mut self) {
+        for path in self.runs.iter() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Dedup applies a DuplicateKeyPolicy to sorted entries before adding them to a table.
+struct Dedup {
+    policy: DuplicateKeyPolicy,
+    cmp: Arc<Box<dyn Cmp>>,
+    pending: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Dedup {
+    fn new(policy: DuplicateKeyPolicy, cmp: Arc<Box<dyn Cmp>>) -> Dedup {
+        Dedup {
+            policy,
+            cmp,
+            pending: None,
+        }
+    }
+
+    fn add<W: Write>(
+        &mut self,
+        key: Vec<u8>,
+        val: Vec<u8>,
+        builder: &mut TableBuilder<W>,
+    ) -> Result<()> {
+        if let Some((ref pkey, ref mut pval)) = self.pending {
+            if self.cmp.cmp(pkey, &key) == Ordering::Equal {
+                return match self.policy {
+                    DuplicateKeyPolicy::KeepFirst => Ok(()),
+                    DuplicateKeyPolicy::KeepLast => {
+                        *pval = val;
+                        Ok(())
+                    }
+                    DuplicateKeyPolicy::Error => err(
+                        StatusCode::AlreadyExists,
+                        &format!("duplicate key {:?}", String::from_utf8_lossy(&key)),
+                    ),
+                };
+            }
+        }
+        if let Some((pkey, pval)) = self.pending.replace((key, val)) {
+            builder.add(&pkey, &pval)?;
+        }
+        Ok(())
+    }
+
+    fn finish<W: Write>(&mut self, builder: &mut TableBuilder<W>) -> Result<()> {
+        if let Some((key, val)) = self.pending.take() {
+            builder.add(&key, &val)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::SSIteratorIter;
+
+    fn tmp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("sstable-test-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn build(
+        opt: Options,
+        dir: &Path,
+        policy: DuplicateKeyPolicy,
+        entries: &[(Vec<u8>, Vec<u8>)],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut dst = vec![];
+        {
+            let mut b = SortingTableBuilder::new(opt.clone(), &mut dst)
+                .with_duplicate_key_policy(policy)
+                .with_tmp_dir(dir);
+            for (k, v) in entries.iter() {
+                b.add(k, v)?;
+            }
+            b.finish()?;
+        }
+        let size = dst.len();
+        let table = Table::new(opt, Box::new(dst), size)?;
+        let mut iter = table.iter();
+        let result = SSIteratorIter::wrap(&mut iter).collect();
+        Ok(result)
+    }
+
+    #[test]
+    fn test_sorting_table_builder() {
+        // Keys 0..500 in a scrambled order; every key divisible by 7 is added twice.
+        let mut entries = vec![];
+        for i in 0..500 {
+            let k = (i * 263) % 500;
+            entries.push((format!("key{:03}", k).into_bytes(), b"first".to_vec()));
+            if k % 7 == 0 {
+                entries.push((format!("key{:03}", k).into_bytes(), b"second".to_vec()));
+            }
+        }
+        let expected = |dup_val: &[u8]| {
+            (0..500)
+                .map(|k| {
+                    let val: &[u8] = if k % 7 == 0 { dup_val } else { b"first" };
+                    (format!("key{:03}", k).into_bytes(), val.to_vec())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let dir = tmp_dir("sorting");
+        for &write_buffer_size in &[1 << 20, 1000, 10] {
+            let mut opt = Options::default();
+            opt.write_buffer_size = write_buffer_size;
+
+            let first = build(opt.clone(), &dir, DuplicateKeyPolicy::KeepFirst, &entries);
+            assert_eq!(first, Ok(expected(b"first")));
+            let last = build(opt.clone(), &dir, DuplicateKeyPolicy::KeepLast, &entries);
+            assert_eq!(last, Ok(expected(b"second")));
+            let error = build(opt.clone(), &dir, DuplicateKeyPolicy::Error, &entries);
+            assert_eq!(error.unwrap_err().code, StatusCode::AlreadyExists);
+        }
+
+        // All runs have been removed.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+}
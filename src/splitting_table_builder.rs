@@ -0,0 +1,191 @@
+use crate::error::Result;
+use crate::key_types::{SequenceNumber, MAX_SEQUENCE_NUMBER};
+use crate::options::Options;
+use crate::table_builder::TableBuilder;
+
+use std::io::Write;
+
+/// TableInfo describes a table written by a SplittingTableBuilder.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableInfo {
+    /// The size of the table in bytes.
+    pub size: usize,
+    pub entries: usize,
+    pub smallest_key: Vec<u8>,
+    pub largest_key: Vec<u8>,
+}
+
+/// SplittingTableBuilder writes sorted entries to a sequence of tables, each of which is at most
+/// about `max_size` bytes large. Once the current table has reached `max_size`, it is finished
+/// at the next data block boundary, and a new one is started. The destination of the n-th table
+/// (counting from 0) is obtained by calling `new_dst(n)`.
+pub struct SplittingTableBuilder<Dst: Write, F: FnMut(usize) -> Result<Dst>> {
+    opt: Options,
+    new_dst: F,
+    max_size: usize,
+    max_entries: usize,
+
+    current: Option<TableBuilder<Dst>>,
+    smallest_key: Vec<u8>,
+    largest_key: Vec<u8>,
+    tables: Vec<TableInfo>,
+    // Ranges deleted with delete_range_at(), which are added to every new table.
+    range_deletions: Vec<(Vec<u8>, Vec<u8>, SequenceNumber)>,
+}
+
+impl<Dst: Write, F: FnMut(usize) -> Result<Dst>> SplittingTableBuilder<Dst, F> {
+    pub fn new(opt: Options, max_size: usize, new_dst: F) -> SplittingTableBuilder<Dst, F> {
+        SplittingTableBuilder {
+            opt,
+            new_dst,
+            max_size,
+            max_entries: usize::MAX,
+            current: None,
+            smallest_key: vec![],
+            largest_key: vec![],
+            tables: vec![],
+            range_deletions: vec![],
+        }
+    }
+
+    pub(crate) fn options(&self) -> &Options {
+        &self.opt
+    }
+
+    /// Limits the number of entries per table. Unlike the size limit, this is a hard limit: the
+    /// current table is finished as soon as it has `max_entries` entries.
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        assert!(max_entries > 0);
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Deletes a range of keys, as `TableBuilder::delete_range()`. The range is added to the
+    /// current table and all following ones; call this before adding entries to cover all tables.
+    pub fn delete_range(&mut self, start: &[u8], end: &[u8]) {
+        self.delete_range_at(start, end, MAX_SEQUENCE_NUMBER);
+    }
+
+    /// Like `delete_range()`, for `TableBuilder::delete_range_at()`.
+    pub fn delete_range_at(&mut self, start: &[u8], end: &[u8], seq: SequenceNumber) {
+        if let Some(ref mut builder) = self.current {
+            builder.delete_range_at(start, end, seq);
+        }
+        self.range_deletions
+            .push((start.to_vec(), end.to_vec(), seq));
+    }
+
+    /// Add an entry. The key must be greater than the previously added one, as with
+    /// `TableBuilder::add()`.
+    pub fn add(&mut self, key: &[u8], val: &[u8]) -> Result<()> {
+        if let Some(ref builder) = self.current {
+            if builder.entries() >= self.max_entries
+                || (builder.size_estimate() >= self.max_size && builder.at_block_boundary())
+            {
+                self.finish_table()?;
+            }
+        }
+
+        if self.current.is_none() {
+            self.start_table()?;
+            self.smallest_key = key.to_vec();
+        }
+        self.current.as_mut().unwrap().add(key, val)?;
+        self.largest_key.clear();
+        self.largest_key.extend_from_slice(key);
+        Ok(())
+    }
+
+    fn start_table(&mut self) -> Result<()> {
+        let dst = (self.new_dst)(self.tables.len())?;
+        let mut builder = TableBuilder::new(self.opt.clone(), dst);
+        for (start, end, seq) in self.range_deletions.iter() {
+            builder.delete_range_at(start, end, *seq);
+        }
+        self.current = Some(builder);
+        Ok(())
+    }
+
+    fn finish_table(&mut self) -> Result<()> {
+        if let Some(builder) = self.current.take() {
+            let entries = builder.entries();
+            let size = builder.finish()?;
+            self.tables.push(TableInfo {
+                size,
+                entries,
+                smallest_key: std::mem::take(&mut self.smallest_key),
+                largest_key: self.largest_key.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Finishes the current table, and returns information on all tables that have been written.
+    /// No table is written if no entries were added, unless ranges were deleted: then a table
+    /// containing only the range deletions is written.
+    pub fn finish(mut self) -> Result<Vec<TableInfo>> {
+        if self.tables.is_empty() && self.current.is_none() && !self.range_deletions.is_empty() {
+            self.start_table()?;
+        }
+        self.finish_table()?;
+        Ok(self.tables)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table_reader::Table;
+    use crate::test_util::SSIteratorIter;
+
+    use std::fs;
+
+    #[test]
+    fn test_splitting_table_builder() {
+        let dir =
+            std::env::temp_dir().join(format!("sstable-test-splitting-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |i: usize| dir.join(format!("{:03}.sst", i));
+
+        let data: Vec<(Vec<u8>, Vec<u8>)> = (0..1000)
+            .map(|i| (format!("key{:04}", i).into_bytes(), vec![b'x'; 20]))
+            .collect();
+        let mut opt = Options::default();
+        opt.block_size = 256;
+
+        for &(max_size, max_entries) in &[(4096, usize::MAX), (1 << 20, 300), (0, usize::MAX)] {
+            let mut b = SplittingTableBuilder::new(opt.clone(), max_size, |i| {
+                Ok(fs::File::create(path(i))?)
+            })
+            .with_max_entries(max_entries);
+            for (k, v) in data.iter() {
+                b.add(k, v).unwrap();
+            }
+            let tables = b.finish().unwrap();
+            assert!(tables.len() > 1);
+
+            let mut all = vec![];
+            for (i, info) in tables.iter().enumerate() {
+                assert!(info.entries <= max_entries);
+                if i + 1 < tables.len() && max_entries == usize::MAX {
+                    assert!(info.size >= max_size);
+                    assert!(info.size < max_size + 2 * opt.block_size);
+                }
+                let table = Table::new_from_file(opt.clone(), &path(i)).unwrap();
+                let mut iter = table.iter();
+                let entries = SSIteratorIter::wrap(&mut iter).collect::<Vec<_>>();
+                assert_eq!(fs::metadata(path(i)).unwrap().len() as usize, info.size);
+                assert_eq!(entries.len(), info.entries);
+                assert_eq!(entries.first().unwrap().0, info.smallest_key);
+                assert_eq!(entries.last().unwrap().0, info.largest_key);
+                all.extend(entries);
+                fs::remove_file(path(i)).unwrap();
+            }
+            assert_eq!(all, data);
+        }
+
+        let b = SplittingTableBuilder::new(opt, 1, |i| Ok(fs::File::create(path(i))?));
+        assert_eq!(b.finish(), Ok(vec![]));
+        fs::remove_dir(&dir).unwrap();
+    }
+}
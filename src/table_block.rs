@@ -3,16 +3,19 @@ use crate::blockhandle::BlockHandle;
 use crate::error::{err, Result, StatusCode};
 use crate::filter;
 use crate::filter_block::FilterBlockReader;
-use crate::options::{self, CompressionType, Options};
-use crate::table_builder;
-use crate::types::{unmask_crc, RandomAccess};
+use crate::options::{self, ChecksumType, CompressionType, Options};
+use crate::table_builder::{self, CRC32C};
+use crate::types::{mask_crc, RandomAccess};
 
-use crc::{Crc, CRC_32_ISCSI};
 use integer_encoding::FixedInt;
 use snap::raw::Decoder;
+use xxhash_rust::{xxh3, xxh32, xxh64};
+
+/// Used by the XXH3 checksum to include the compression type byte, as in RocksDB.
+const XXH3_LAST_BYTE_PRIME: u32 = 0x6b9083d9;
 
 /// Reads the data for the specified block handle from a file.
-fn read_bytes(f: &dyn RandomAccess, location: &BlockHandle) -> Result<Vec<u8>> {
+pub fn read_bytes(f: &dyn RandomAccess, location: &BlockHandle) -> Result<Vec<u8>> {
     let mut buf = vec![0; location.size()];
     f.read_at(location.offset(), &mut buf).map(|_| buf)
 }
@@ -35,11 +38,13 @@ pub fn read_filter_block(
 
 /// Reads a table block from a random-access source.
 /// A table block consists of [bytes..., compress (1B), checksum (4B)]; the handle only refers to
-/// the location and length of [bytes...].
+/// the location and length of [bytes...]. The checksum is only checked if `verify_checksum` is
+/// set; its type is `opt.checksum_type`.
 pub fn read_table_block(
     opt: Options,
     f: &dyn RandomAccess,
     location: &BlockHandle,
+    verify_checksum: bool,
 ) -> Result<Block> {
     // The block is denoted by offset and length in BlockHandle. A block in an encoded
     // table is followed by 1B compression type and 4B checksum.
@@ -57,7 +62,14 @@ pub fn read_table_block(
     let mut compress = buf.split_off(block_data_size);
     let cksum = compress.split_off(table_builder::TABLE_BLOCK_COMPRESS_LEN);
 
-    if !verify_table_block(&buf, compress[0], unmask_crc(u32::decode_fixed(&cksum))) {
+    if verify_checksum
+        && !verify_table_block(
+            opt.checksum_type,
+            &buf,
+            compress[0],
+            u32::decode_fixed(&cksum),
+        )
+    {
         return err(
             StatusCode::Corruption,
             &format!(
@@ -80,11 +92,36 @@ pub fn read_table_block(
     }
 }
 
+/// Computes the checksum stored in the trailer of a block with contents `data` and compression
+/// type byte `compression`.
+pub fn block_checksum(ctype: ChecksumType, data: &[u8], compression: u8) -> u32 {
+    match ctype {
+        ChecksumType::NoChecksum => 0,
+        ChecksumType::Crc32c => {
+            let mut digest = CRC32C.digest();
+            digest.update(data);
+            digest.update(&[compression; 1]);
+            mask_crc(digest.finalize())
+        }
+        ChecksumType::XxHash => {
+            let mut hasher = xxh32::Xxh32::new(0);
+            hasher.update(data);
+            hasher.update(&[compression; 1]);
+            hasher.digest()
+        }
+        ChecksumType::XxHash64 => {
+            let mut hasher = xxh64::Xxh64::new(0);
+            hasher.update(data);
+            hasher.update(&[compression; 1]);
+            hasher.digest() as u32
+        }
+        ChecksumType::Xxh3 => {
+            xxh3::xxh3_64(data) as u32 ^ (compression as u32).wrapping_mul(XXH3_LAST_BYTE_PRIME)
+        }
+    }
+}
+
 /// Verify checksum of block
-fn verify_table_block(data: &[u8], compression: u8, want: u32) -> bool {
-    let crc = Crc::<u32>::new(&CRC_32_ISCSI);
-    let mut digest = crc.digest();
-    digest.update(data);
-    digest.update(&[compression; 1]);
-    digest.finalize() == want
+fn verify_table_block(ctype: ChecksumType, data: &[u8], compression: u8, want: u32) -> bool {
+    ctype == ChecksumType::NoChecksum || block_checksum(ctype, data, compression) == want
 }
@@ -1,24 +1,71 @@
+use crate::blob::{self, BlobWriter};
 use crate::block::BlockContents;
 use crate::block_builder::BlockBuilder;
 use crate::blockhandle::BlockHandle;
-use crate::error::Result;
+use crate::cmp;
+use crate::error::{err, Result, StatusCode};
 use crate::filter::NoFilterPolicy;
 use crate::filter_block::FilterBlockBuilder;
-use crate::options::{CompressionType, Options};
-use crate::types::mask_crc;
+use crate::key_types::{SequenceNumber, MAX_SEQUENCE_NUMBER};
+use crate::options::{
+    self, BlockFormat, ChecksumType, CompressionType, DataBlockIndexType, FilterType, IndexType,
+    Options,
+};
+use crate::properties::{TableProperties, PROPERTIES_BLOCK_KEY};
+use crate::range_deletions::{self, RangeDeletions, RANGE_DELETIONS_BLOCK_KEY};
+use crate::table_block;
 
 use std::cmp::Ordering;
 use std::io::Write;
 use std::sync::Arc;
 
-use crc::{Crc, CRC_32_ISCSI};
-use integer_encoding::FixedIntWriter;
+use crc::{Crc, Digest, CRC_32_ISCSI};
+use integer_encoding::{FixedInt, VarInt};
 use snap::raw::Encoder;
 
 pub const FOOTER_LENGTH: usize = 40;
 pub const FULL_FOOTER_LENGTH: usize = FOOTER_LENGTH + 8;
 const MAGIC_FOOTER_ENCODED: [u8; 8] = [0x57, 0xfb, 0x80, 0x8b, 0x24, 0x75, 0x47, 0xdb];
 
+/// A versioned footer contains the file length and checksum, the checksum type, the feature flags
+/// and the format version (fixed u64, u64, u32, u32, u32) before the magic number.
+pub const VERSIONED_FOOTER_LENGTH: usize = FOOTER_LENGTH + 16 + 4 + 4 + 4 + 8;
+const MAGIC_VERSIONED_FOOTER_ENCODED: [u8; 8] = [0x59, 0xfb, 0x80, 0x8b, 0x24, 0x75, 0x47, 0xdb];
+
+/// Tables written by RocksDB (see the `rocksdb` module) have a footer of this length, with room
+/// for two block handles of up to 20 bytes each.
+pub const ROCKSDB_FOOTER_LENGTH: usize = 1 + 2 * 20 + 4 + 8;
+const MAGIC_ROCKSDB_FOOTER_ENCODED: [u8; 8] = [0xf7, 0xcf, 0xf4, 0x85, 0xb7, 0x41, 0xe2, 0x88];
+/// The RocksDB format versions whose tables can be read.
+const ROCKSDB_FORMAT_VERSIONS: std::ops::RangeInclusive<u32> = 2..=5;
+
+/// The length of the longest footer that can be decoded.
+pub const MAX_FOOTER_LENGTH: usize = VERSIONED_FOOTER_LENGTH;
+
+/// Format version 0: LevelDB tables, with the original footer.
+pub const FORMAT_VERSION_LEVELDB: u32 = 0;
+/// The format version of tables with a versioned footer, written when LevelDB can't read a table.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The index block is a top-level index pointing to index partitions.
+pub const FEATURE_PARTITIONED_INDEX: u32 = 1 << 0;
+/// Data blocks may have a hash index (see `block::Block`).
+pub const FEATURE_DATA_BLOCK_HASH_INDEX: u32 = 1 << 1;
+/// All blocks have the `BlockFormat::Compact` format.
+pub const FEATURE_COMPACT_BLOCKS: u32 = 1 << 2;
+/// Values are tagged, and may be stored in a blob file (see the `blob` module).
+pub const FEATURE_BLOB_VALUES: u32 = 1 << 3;
+/// The footer contains the length and checksum of the file up to the footer.
+pub const FEATURE_FILE_CHECKSUM: u32 = 1 << 4;
+const SUPPORTED_FEATURES: u32 = FEATURE_PARTITIONED_INDEX
+    | FEATURE_DATA_BLOCK_HASH_INDEX
+    | FEATURE_COMPACT_BLOCKS
+    | FEATURE_BLOB_VALUES
+    | FEATURE_FILE_CHECKSUM;
+
+/// Used for the whole-file checksum; a static, so that a running digest can be kept.
+pub(crate) static CRC32C: Crc<u32> = Crc::<u32>::new(&CRC_32_ISCSI);
+
 pub const TABLE_BLOCK_COMPRESS_LEN: usize = 1;
 pub const TABLE_BLOCK_CKSUM_LEN: usize = 4;
 
@@ -27,50 +74,201 @@ pub const TABLE_BLOCK_CKSUM_LEN: usize = 4;
 pub struct Footer {
     pub meta_index: BlockHandle,
     pub index: BlockHandle,
+    /// `FORMAT_VERSION_LEVELDB` or `FORMAT_VERSION`; determines the encoding of the footer.
+    pub format_version: u32,
+    /// Feature flags (`FEATURE_*`) of table formats that are not LevelDB-compatible.
+    pub features: u32,
+    /// Length and CRC32C of the file up to the footer, if FEATURE_FILE_CHECKSUM is set. The
+    /// footer itself, including its block handles, isn't covered by the checksum.
+    pub file_checksum: Option<(usize, u64)>,
+    /// The checksum type of all blocks; always CRC32C in LevelDB tables.
+    pub checksum_type: ChecksumType,
+    /// The format version of a table written by RocksDB; None for all other tables.
+    pub rocksdb_format_version: Option<u32>,
 }
 
 /// A Table footer contains a pointer to the metaindex block, another pointer to the index block,
 /// and a magic number:
 /// [ { table data ... , METAINDEX blockhandle, INDEX blockhandle, PADDING bytes } = 40 bytes,
 /// MAGIC_FOOTER_ENCODED ]
+///
+/// Tables using features unknown to LevelDB (including checksum types other than CRC32C) have a
+/// versioned footer instead, which older readers
+/// reject because of its different magic number:
+/// [ { METAINDEX blockhandle, INDEX blockhandle, PADDING bytes } = 40 bytes, FILE LENGTH (u64),
+/// FILE CHECKSUM (u64), CHECKSUM TYPE (u32), FEATURES (u32), FORMAT VERSION (u32),
+/// MAGIC_VERSIONED_FOOTER_ENCODED ]
+///
+/// The file length and checksum are 0 unless FEATURE_FILE_CHECKSUM is set. The format version is
+/// checked before anything else, so that later versions can change the rest of the footer.
+///
+/// Tables written by RocksDB (format versions 2 to 5) have a footer of their own:
+/// [ CHECKSUM TYPE (u8), { METAINDEX blockhandle, INDEX blockhandle, PADDING bytes } = 40 bytes,
+/// ROCKSDB FORMAT VERSION (u32), MAGIC_ROCKSDB_FOOTER_ENCODED ]
 impl Footer {
     pub fn new(metaix: BlockHandle, index: BlockHandle) -> Footer {
         Footer {
             meta_index: metaix,
-            index: index,
+            index,
+            format_version: FORMAT_VERSION_LEVELDB,
+            features: 0,
+            file_checksum: None,
+            checksum_type: ChecksumType::Crc32c,
+            rocksdb_format_version: None,
         }
     }
 
-    pub fn decode(from: &[u8]) -> Footer {
-        assert!(from.len() >= FULL_FOOTER_LENGTH);
-        assert_eq!(&from[FOOTER_LENGTH..], &MAGIC_FOOTER_ENCODED);
-        let (meta, metalen) = BlockHandle::decode(&from[0..]);
-        let (ix, _) = BlockHandle::decode(&from[metalen..]);
+    pub fn has_feature(&self, feature: u32) -> bool {
+        self.features & feature != 0
+    }
 
-        Footer {
-            meta_index: meta,
-            index: ix,
+    /// Returns the names of the features used by the table.
+    pub fn feature_names(&self) -> Vec<&'static str> {
+        [
+            (FEATURE_PARTITIONED_INDEX, "partitioned_index"),
+            (FEATURE_DATA_BLOCK_HASH_INDEX, "data_block_hash_index"),
+            (FEATURE_COMPACT_BLOCKS, "compact_blocks"),
+            (FEATURE_BLOB_VALUES, "blob_values"),
+            (FEATURE_FILE_CHECKSUM, "file_checksum"),
+        ]
+        .iter()
+        .filter(|(feature, _)| self.has_feature(*feature))
+        .map(|(_, name)| *name)
+        .collect()
+    }
+
+    /// Returns the length of the encoded footer.
+    pub fn encoded_length(&self) -> usize {
+        if self.rocksdb_format_version.is_some() {
+            return ROCKSDB_FOOTER_LENGTH;
+        }
+        match self.format_version {
+            FORMAT_VERSION_LEVELDB => FULL_FOOTER_LENGTH,
+            _ => VERSIONED_FOOTER_LENGTH,
         }
     }
 
-    pub fn encode(&self, to: &mut [u8]) {
-        assert!(to.len() >= FOOTER_LENGTH + 8);
+    /// Decodes a footer from the end of `from`, which should contain the last
+    /// `MAX_FOOTER_LENGTH` bytes of a table (or the whole table, if it is shorter).
+    pub fn decode(from: &[u8]) -> Result<Footer> {
+        if from.len() < FULL_FOOTER_LENGTH {
+            return err(StatusCode::Corruption, "table too short for footer");
+        }
+        let magic = &from[from.len() - 8..];
+        let fixed_u32_before_magic =
+            |n: usize| u32::decode_fixed(&from[from.len() - 8 - 4 * n..from.len() - 4 - 4 * n]);
+
+        let mut footer = Footer::new(BlockHandle::new(0, 0), BlockHandle::new(0, 0));
+        let (handles, supported_features) = if magic == MAGIC_FOOTER_ENCODED {
+            (&from[from.len() - FULL_FOOTER_LENGTH..], 0)
+        } else if magic == MAGIC_VERSIONED_FOOTER_ENCODED {
+            footer.format_version = fixed_u32_before_magic(1);
+            if footer.format_version != FORMAT_VERSION {
+                return err(
+                    StatusCode::NotSupported,
+                    &format!("unsupported table format version {}", footer.format_version),
+                );
+            }
+            if from.len() < VERSIONED_FOOTER_LENGTH {
+                return err(StatusCode::Corruption, "table too short for footer");
+            }
+            footer.features = fixed_u32_before_magic(2);
+            footer.checksum_type = decode_checksum_type(fixed_u32_before_magic(3), false)?;
+            let handles = &from[from.len() - VERSIONED_FOOTER_LENGTH..];
+            if footer.has_feature(FEATURE_FILE_CHECKSUM) {
+                let length = u64::decode_fixed(&handles[FOOTER_LENGTH..FOOTER_LENGTH + 8]);
+                let checksum = u64::decode_fixed(&handles[FOOTER_LENGTH + 8..FOOTER_LENGTH + 16]);
+                footer.file_checksum = Some((length as usize, checksum));
+            }
+            (handles, SUPPORTED_FEATURES)
+        } else if magic == MAGIC_ROCKSDB_FOOTER_ENCODED {
+            let version = fixed_u32_before_magic(1);
+            if !ROCKSDB_FORMAT_VERSIONS.contains(&version) {
+                return err(
+                    StatusCode::NotSupported,
+                    &format!("unsupported RocksDB format version {}", version),
+                );
+            }
+            if from.len() < ROCKSDB_FOOTER_LENGTH {
+                return err(StatusCode::Corruption, "table too short for footer");
+            }
+            footer.rocksdb_format_version = Some(version);
+            let footer_bytes = &from[from.len() - ROCKSDB_FOOTER_LENGTH..];
+            footer.checksum_type = decode_checksum_type(footer_bytes[0] as u32, true)?;
+            (&footer_bytes[1..], 0)
+        } else {
+            return err(StatusCode::Corruption, "bad magic number in footer");
+        };
+
+        if footer.features & !supported_features != 0 {
+            return err(
+                StatusCode::NotSupported,
+                &format!("unsupported table features {:#x}", footer.features),
+            );
+        }
+
+        let (meta, metalen) = BlockHandle::decode(handles);
+        let (ix, _) = BlockHandle::decode(&handles[metalen..]);
+        footer.meta_index = meta;
+        footer.index = ix;
+        Ok(footer)
+    }
 
+    /// Encodes the footer into `to`, which must be at least `encoded_length()` bytes long.
+    pub fn encode(&self, to: &mut [u8]) {
+        assert!(to.len() >= self.encoded_length());
+
+        if let Some(version) = self.rocksdb_format_version {
+            to[0] = self.checksum_type as u8;
+            let s1 = self.meta_index.encode_to(&mut to[1..]);
+            let s2 = self.index.encode_to(&mut to[1 + s1..]);
+            let to = &mut to[..ROCKSDB_FOOTER_LENGTH];
+            to[1 + s1 + s2..ROCKSDB_FOOTER_LENGTH - 12].fill(0);
+            version.encode_fixed(&mut to[ROCKSDB_FOOTER_LENGTH - 12..ROCKSDB_FOOTER_LENGTH - 8]);
+            to[ROCKSDB_FOOTER_LENGTH - 8..].copy_from_slice(&MAGIC_ROCKSDB_FOOTER_ENCODED);
+            return;
+        }
         let s1 = self.meta_index.encode_to(to);
         let s2 = self.index.encode_to(&mut to[s1..]);
 
-        for i in s1 + s2..FOOTER_LENGTH {
-            to[i] = 0;
-        }
-        for i in FOOTER_LENGTH..FULL_FOOTER_LENGTH {
-            to[i] = MAGIC_FOOTER_ENCODED[i - FOOTER_LENGTH];
+        to[s1 + s2..FOOTER_LENGTH].fill(0);
+        match self.format_version {
+            FORMAT_VERSION_LEVELDB => {
+                to[FOOTER_LENGTH..FULL_FOOTER_LENGTH].copy_from_slice(&MAGIC_FOOTER_ENCODED);
+            }
+            _ => {
+                let (length, checksum) = self.file_checksum.unwrap_or_default();
+                let to = &mut to[FOOTER_LENGTH..VERSIONED_FOOTER_LENGTH];
+                (length as u64).encode_fixed(&mut to[0..8]);
+                checksum.encode_fixed(&mut to[8..16]);
+                (self.checksum_type as u32).encode_fixed(&mut to[16..20]);
+                self.features.encode_fixed(&mut to[20..24]);
+                self.format_version.encode_fixed(&mut to[24..28]);
+                to[28..36].copy_from_slice(&MAGIC_VERSIONED_FOOTER_ENCODED);
+            }
         }
     }
 }
 
+/// Only RocksDB writes tables without block checksums (`allow_none`); our own footers never
+/// record `NoChecksum`.
+fn decode_checksum_type(ctype: u32, allow_none: bool) -> Result<ChecksumType> {
+    match options::int_to_checksumtype(ctype) {
+        Some(ChecksumType::NoChecksum) if !allow_none => err(
+            StatusCode::Corruption,
+            "checksum type 0 (none) in table footer",
+        ),
+        Some(ctype) => Ok(ctype),
+        None => err(
+            StatusCode::NotSupported,
+            &format!("unsupported checksum type {}", ctype),
+        ),
+    }
+}
+
 /// A TableBuilder is used to create a table from a set of sorted string pairs and write it to a
 /// file or a buffer.
-
+//
 // A table consists of DATA BLOCKs, META BLOCKs, a METAINDEX BLOCK, an INDEX BLOCK and a FOOTER.
 //
 // DATA BLOCKs, META BLOCKs, INDEX BLOCK and METAINDEX BLOCK are built using the code in
@@ -79,18 +277,64 @@ impl Footer {
 // The FOOTER consists of a BlockHandle that points to the metaindex block, another pointing to
 // the index block, padding to fill up to 40 B and at the end the 8B magic number
 // 0xdb4775248b80fb57.
-pub struct TableBuilder<Dst: Write> {
+//
+// With a partitioned index (`IndexType::Partitioned`), the index entries are written to INDEX
+// PARTITION blocks of about `block_size` bytes in between the data blocks, and the INDEX BLOCK
+// contains an entry per partition, mapping its last key to its location. The footer is then
+// a versioned footer with the FEATURE_PARTITIONED_INDEX flag set.
+//
+// With `DataBlockIndexType::BinaryAndHash`, data blocks get a hash index, and the footer is a
+// versioned footer with the FEATURE_DATA_BLOCK_HASH_INDEX flag set. Likewise, tables whose blocks
+// have the compact format (`BlockFormat::Compact`) have the FEATURE_COMPACT_BLOCKS flag set, and
+// tables built with a blob file have the FEATURE_BLOB_VALUES flag set.
+//
+// If `opt.range_filter_prefix_len` is set, a RANGE FILTER block is written after the filter
+// block. It is a per-block filter block over the key prefixes of `range_filter_prefix_len` bytes,
+// and its metaindex entry ("prefixfilter.<policy name>") contains the prefix length after the
+// block handle.
+//
+// If ranges were deleted with `delete_range()`, a RANGE DELETIONS block (see the
+// `range_deletions` module) is written after the filter blocks.
+//
+// If `opt.properties_block` is set, a PROPERTIES block (see the `properties` module) is written
+// after the filter blocks and the range deletions.
+pub struct TableBuilder<Dst: Write, BlobDst: Write = Dst> {
     opt: Options,
     dst: Dst,
 
-    crc: Crc<u32>,
     offset: usize,
-    num_entries: usize,
+    // Checksum of everything written so far, if `opt.file_checksum` is set.
+    file_digest: Option<Digest<'static, u32>>,
+    // Statistics written to the properties block.
+    props: TableProperties,
+    // An upper bound for the size of the properties block including its trailer; 0 if
+    // `opt.properties_block` isn't set.
+    max_props_size: usize,
     prev_block_last_key: Vec<u8>,
 
     data_block: Option<BlockBuilder>,
+    // The current index partition if the index is partitioned.
     index_block: Option<BlockBuilder>,
+    // Only used with a partitioned index.
+    top_index_block: Option<BlockBuilder>,
     filter_block: Option<FilterBlockBuilder>,
+    prefix_filter_block: Option<FilterBlockBuilder>,
+    // The last prefix added to the range filter in the current data block.
+    last_prefix: Vec<u8>,
+    // Ranges deleted with `delete_range()`, in the order they were added.
+    range_deletions: Vec<(Vec<u8>, Vec<u8>)>,
+
+    blob_file: Option<BlobWriter<BlobDst>>,
+    // Buffer for tagged values.
+    tagged_val: Vec<u8>,
+}
+
+fn new_data_block(opt: &Options) -> BlockBuilder {
+    let builder = BlockBuilder::new(opt.clone());
+    match opt.data_block_index_type {
+        DataBlockIndexType::BinarySearch => builder,
+        DataBlockIndexType::BinaryAndHash => builder.with_hash_index(opt.data_block_hash_ratio),
+    }
 }
 
 impl<Dst: Write> TableBuilder<Dst> {
@@ -98,29 +342,85 @@ impl<Dst: Write> TableBuilder<Dst> {
         opt.filter_policy = Arc::new(Box::new(NoFilterPolicy::new()));
         TableBuilder::new(opt, dst)
     }
+
+    /// Create a new table builder.
+    pub fn new(opt: Options, dst: Dst) -> TableBuilder<Dst> {
+        TableBuilder::with_blob_writer(opt, dst, None)
+    }
 }
 
 /// TableBuilder is used for building a new SSTable. It groups entries into blocks,
 /// calculating checksums and bloom filters.
-impl<Dst: Write> TableBuilder<Dst> {
-    /// Create a new table builder.
-    pub fn new(opt: Options, dst: Dst) -> TableBuilder<Dst> {
+impl<Dst: Write, BlobDst: Write> TableBuilder<Dst, BlobDst> {
+    /// Create a new table builder that writes values larger than `opt.blob_value_threshold` to
+    /// `blob_file` instead of the table. The table can only be read completely if the blob file
+    /// is passed to `Table::new_with_blob_file()`.
+    pub fn new_with_blob_file(
+        opt: Options,
+        dst: Dst,
+        blob_file: BlobDst,
+    ) -> TableBuilder<Dst, BlobDst> {
+        TableBuilder::with_blob_writer(opt, dst, Some(BlobWriter::new(blob_file)))
+    }
+
+    fn with_blob_writer(
+        opt: Options,
+        dst: Dst,
+        blob_file: Option<BlobWriter<BlobDst>>,
+    ) -> TableBuilder<Dst, BlobDst> {
+        assert!(
+            opt.checksum_type != ChecksumType::NoChecksum,
+            "tables can't be written without block checksums"
+        );
         TableBuilder {
             opt: opt.clone(),
-            dst: dst,
-            crc: Crc::<u32>::new(&CRC_32_ISCSI),
+            dst,
             offset: 0,
+            file_digest: if opt.file_checksum {
+                Some(CRC32C.digest())
+            } else {
+                None
+            },
             prev_block_last_key: vec![],
-            num_entries: 0,
-            data_block: Some(BlockBuilder::new(opt.clone())),
-            filter_block: Some(FilterBlockBuilder::new(opt.filter_policy.clone())),
-            index_block: Some(BlockBuilder::new(opt)),
+            props: TableProperties::default(),
+            max_props_size: if opt.properties_block {
+                TableProperties::max_encoded_size(&opt)
+                    + TABLE_BLOCK_COMPRESS_LEN
+                    + TABLE_BLOCK_CKSUM_LEN
+            } else {
+                0
+            },
+            data_block: Some(new_data_block(&opt)),
+            filter_block: Some(match opt.filter_type {
+                FilterType::PerBlock => FilterBlockBuilder::new(opt.filter_policy.clone()),
+                FilterType::Full => FilterBlockBuilder::new_full(opt.filter_policy.clone()),
+            }),
+            prefix_filter_block: if opt.range_filter_prefix_len > 0 {
+                Some(FilterBlockBuilder::new(opt.filter_policy.clone()))
+            } else {
+                None
+            },
+            last_prefix: vec![],
+            range_deletions: vec![],
+            blob_file,
+            tagged_val: vec![],
+            index_block: Some(BlockBuilder::new(opt.clone())),
+            top_index_block: match opt.index_type {
+                IndexType::SingleLevel => None,
+                IndexType::Partitioned => Some(BlockBuilder::new(opt)),
+            },
         }
     }
 
     /// Returns the current number of entries.
     pub fn entries(&self) -> usize {
-        self.num_entries
+        self.props.num_entries
+    }
+
+    /// Returns true if the next call to `add()` starts a new data block.
+    pub(crate) fn at_block_boundary(&self) -> bool {
+        let dblock = self.data_block.as_ref().unwrap();
+        dblock.entries() == 0 || dblock.size_estimate() > self.opt.block_size
     }
 
     /// Returns the estimated size of the SSTable in bytes. It includes already written
@@ -138,13 +438,27 @@ impl<Dst: Write> TableBuilder<Dst> {
             .as_ref()
             .map(|b| b.size_estimate())
             .unwrap_or(0);
+        size += self
+            .top_index_block
+            .as_ref()
+            .map(|b| b.size_estimate())
+            .unwrap_or(0);
         size += self
             .filter_block
             .as_ref()
             .map(|b| b.size_estimate())
             .unwrap_or(0);
+        size += self
+            .prefix_filter_block
+            .as_ref()
+            .map(|b| b.size_estimate())
+            .unwrap_or(0);
+        if !self.range_deletions.is_empty() {
+            size += range_deletions::max_encoded_size(&self.range_deletions);
+        }
+        size += self.max_props_size;
         size += self.offset;
-        size += FULL_FOOTER_LENGTH;
+        size += MAX_FOOTER_LENGTH;
         size
     }
 
@@ -163,15 +477,63 @@ impl<Dst: Write> TableBuilder<Dst> {
 
         let dblock = &mut self.data_block.as_mut().unwrap();
 
+        self.props.raw_key_size += key.len();
+        self.props.raw_value_size += val.len();
+        let val = if let Some(ref mut blob_file) = self.blob_file {
+            self.tagged_val.clear();
+            if val.len() > self.opt.blob_value_threshold {
+                blob_file.add(val, &mut self.tagged_val)?;
+            } else {
+                blob::tag_inline(val, &mut self.tagged_val);
+            }
+            &self.tagged_val
+        } else {
+            val
+        };
+
         if let Some(ref mut fblock) = self.filter_block {
             fblock.add_key(key);
         }
+        if let Some(ref mut pfblock) = self.prefix_filter_block {
+            // Keys shorter than the prefix length are added completely.
+            let prefix = &key[..key.len().min(self.opt.range_filter_prefix_len)];
+            if prefix != self.last_prefix.as_slice() {
+                pfblock.add_key(prefix);
+                self.last_prefix.clear();
+                self.last_prefix.extend_from_slice(prefix);
+            }
+        }
 
-        self.num_entries += 1;
+        self.props.num_entries += 1;
         dblock.add(key, val);
         Ok(())
     }
 
+    /// Deletes all keys in [start, end) (according to `opt.cmp`), including keys added to this
+    /// table. Ranges can be added at any time before `finish()`, in any order, and may overlap.
+    /// The deleted keys are hidden by `Table::get()`, and by iterators if
+    /// `ReadOptions::hide_range_deletions` is set.
+    ///
+    /// In a table with internal keys, `start` and `end` are user keys, and all versions of the
+    /// user keys in the range are deleted, at every sequence number; see `delete_range_at()`.
+    pub fn delete_range(&mut self, start: &[u8], end: &[u8]) {
+        self.delete_range_at(start, end, MAX_SEQUENCE_NUMBER);
+    }
+
+    /// In a table with internal keys, deletes the versions of the user keys in [start, end) (which
+    /// are ordered by the user comparator) with a sequence number smaller than `seq`, in this
+    /// table and in older tables. `Table::get_at()` returns `LookupResult::Deleted` for such keys
+    /// if the deletion is visible at the lookup's sequence number. In other tables, `seq` is
+    /// ignored.
+    pub fn delete_range_at(&mut self, start: &[u8], end: &[u8], seq: SequenceNumber) {
+        if cmp::is_internal_key_cmp(self.opt.cmp.as_ref().as_ref()) {
+            self.range_deletions
+                .push(range_deletions::internal_range(start, end, seq));
+        } else {
+            self.range_deletions.push((start.to_vec(), end.to_vec()));
+        }
+    }
+
     /// Writes an index entry for the current data_block where `next_key` is the first key of the
     /// next block.
     /// Calls write_block() for writing the block to disk.
@@ -179,26 +541,58 @@ impl<Dst: Write> TableBuilder<Dst> {
         assert!(self.data_block.is_some());
 
         let block = self.data_block.take().unwrap();
-        let sep = self.opt.cmp.find_shortest_sep(&block.last_key(), next_key);
+        let sep = self.opt.cmp.find_shortest_sep(block.last_key(), next_key);
         self.prev_block_last_key = Vec::from(block.last_key());
         let contents = block.finish();
 
         let ctype = self.opt.compression_type;
         let handle = self.write_block(contents, ctype)?;
+        self.props.num_data_blocks += 1;
+        self.props.data_size += handle.size() + TABLE_BLOCK_COMPRESS_LEN + TABLE_BLOCK_CKSUM_LEN;
 
-        let mut handle_enc = [0 as u8; 16];
+        let mut handle_enc = [0_u8; 16];
         let enc_len = handle.encode_to(&mut handle_enc);
 
         self.index_block
             .as_mut()
             .unwrap()
             .add(&sep, &handle_enc[0..enc_len]);
-        self.data_block = Some(BlockBuilder::new(self.opt.clone()));
+        self.data_block = Some(new_data_block(&self.opt));
+
+        if self.top_index_block.is_some()
+            && self.index_block.as_ref().unwrap().size_estimate() > self.opt.block_size
+        {
+            self.write_index_partition()?;
+        }
 
         if let Some(ref mut fblock) = self.filter_block {
             fblock.start_block(self.offset);
         }
+        if let Some(ref mut pfblock) = self.prefix_filter_block {
+            pfblock.start_block(self.offset);
+            self.last_prefix.clear();
+        }
+
+        Ok(())
+    }
+
+    /// Writes the current index partition and adds an entry for it to the top-level index block.
+    fn write_index_partition(&mut self) -> Result<()> {
+        let partition = self.index_block.take().unwrap();
+        let last_key = partition.last_key().to_vec();
+        let contents = partition.finish();
+
+        let ctype = self.opt.compression_type;
+        let handle = self.write_block(contents, ctype)?;
+
+        let mut handle_enc = [0_u8; 16];
+        let enc_len = handle.encode_to(&mut handle_enc);
 
+        self.top_index_block
+            .as_mut()
+            .unwrap()
+            .add(&last_key, &handle_enc[0..enc_len]);
+        self.index_block = Some(BlockBuilder::new(self.opt.clone()));
         Ok(())
     }
 
@@ -210,14 +604,12 @@ impl<Dst: Write> TableBuilder<Dst> {
             data = encoder.compress_vec(&data)?;
         }
 
-        let mut digest = self.crc.digest();
-
-        digest.update(&data);
-        digest.update(&[ctype as u8; TABLE_BLOCK_COMPRESS_LEN]);
-
-        self.dst.write(&data)?;
-        self.dst.write(&[ctype as u8; TABLE_BLOCK_COMPRESS_LEN])?;
-        self.dst.write_fixedint(mask_crc(digest.finalize()))?;
+        let mut cksum = [0; TABLE_BLOCK_CKSUM_LEN];
+        table_block::block_checksum(self.opt.checksum_type, &data, ctype as u8)
+            .encode_fixed(&mut cksum);
+        self.write_all(&data)?;
+        self.write_all(&[ctype as u8; TABLE_BLOCK_COMPRESS_LEN])?;
+        self.write_all(&cksum)?;
 
         let handle = BlockHandle::new(self.offset, data.len());
         self.offset += data.len() + TABLE_BLOCK_COMPRESS_LEN + TABLE_BLOCK_CKSUM_LEN;
@@ -225,6 +617,15 @@ impl<Dst: Write> TableBuilder<Dst> {
         Ok(handle)
     }
 
+    /// Writes `data` to the file, adding it to the file checksum.
+    fn write_all(&mut self, data: &[u8]) -> Result<()> {
+        if let Some(ref mut digest) = self.file_digest {
+            digest.update(data);
+        }
+        self.dst.write_all(data)?;
+        Ok(())
+    }
+
     pub fn finish(mut self) -> Result<usize> {
         assert!(self.data_block.is_some());
         let ctype = self.opt.compression_type;
@@ -239,8 +640,8 @@ impl<Dst: Write> TableBuilder<Dst> {
             self.write_data_block(&key_past_last)?;
         }
 
-        // Create metaindex block
-        let mut meta_ix_block = BlockBuilder::new(self.opt.clone());
+        // Collect the metaindex entries; they are sorted before building the metaindex block.
+        let mut meta_entries: Vec<(Vec<u8>, Vec<u8>)> = vec![];
 
         if self.filter_block.is_some() {
             // if there's a filter block, write the filter block and add it to the metaindex block.
@@ -249,26 +650,102 @@ impl<Dst: Write> TableBuilder<Dst> {
             let fblock_data = fblock.finish();
             let fblock_handle = self.write_block(fblock_data, CompressionType::CompressionNone)?;
 
-            let mut handle_enc = [0 as u8; 16];
+            let mut handle_enc = [0_u8; 16];
             let enc_len = fblock_handle.encode_to(&mut handle_enc);
 
-            meta_ix_block.add(filter_key.as_bytes(), &handle_enc[0..enc_len]);
+            meta_entries.push((filter_key.into_bytes(), handle_enc[0..enc_len].to_vec()));
+        }
+
+        if let Some(pfblock) = self.prefix_filter_block.take() {
+            let filter_key = format!("prefixfilter.{}", pfblock.filter_name());
+            let pfblock_data = pfblock.finish();
+            let pfblock_handle =
+                self.write_block(pfblock_data, CompressionType::CompressionNone)?;
+
+            let mut handle_enc = [0_u8; 16 + 10];
+            let mut enc_len = pfblock_handle.encode_to(&mut handle_enc);
+            enc_len += self
+                .opt
+                .range_filter_prefix_len
+                .encode_var(&mut handle_enc[enc_len..]);
+
+            meta_entries.push((filter_key.into_bytes(), handle_enc[0..enc_len].to_vec()));
+        }
+
+        let ranges = std::mem::take(&mut self.range_deletions);
+        let range_deletions = RangeDeletions::new(self.opt.cmp.as_ref().as_ref(), ranges);
+        if !range_deletions.is_empty() {
+            let block = range_deletions.encode(&self.opt);
+            let handle = self.write_block(block, ctype)?;
+            let mut handle_enc = [0_u8; 16];
+            let enc_len = handle.encode_to(&mut handle_enc);
+            meta_entries.push((
+                RANGE_DELETIONS_BLOCK_KEY.to_vec(),
+                handle_enc[0..enc_len].to_vec(),
+            ));
+        }
+
+        if self.opt.properties_block {
+            let props = self.props.encode(&self.opt);
+            let props_handle = self.write_block(props, ctype)?;
+            let mut handle_enc = [0_u8; 16];
+            let enc_len = props_handle.encode_to(&mut handle_enc);
+            meta_entries.push((
+                PROPERTIES_BLOCK_KEY.to_vec(),
+                handle_enc[0..enc_len].to_vec(),
+            ));
         }
 
         // write metaindex block
+        let cmp = self.opt.cmp.clone();
+        meta_entries.sort_by(|a, b| cmp.cmp(&a.0, &b.0));
+        let mut meta_ix_block = BlockBuilder::new(self.opt.clone());
+        for (key, val) in meta_entries.iter() {
+            meta_ix_block.add(key, val);
+        }
         let meta_ix = meta_ix_block.finish();
         let meta_ix_handle = self.write_block(meta_ix, ctype)?;
 
-        // write index block
+        // write index block; with a partitioned index, write the last partition first.
+        let mut features = 0;
+        if self.top_index_block.is_some() {
+            if self.index_block.as_ref().unwrap().entries() > 0 {
+                self.write_index_partition()?;
+            }
+            self.index_block = self.top_index_block.take();
+            features |= FEATURE_PARTITIONED_INDEX;
+        }
+        if self.opt.data_block_index_type == DataBlockIndexType::BinaryAndHash {
+            features |= FEATURE_DATA_BLOCK_HASH_INDEX;
+        }
+        if self.opt.block_format == BlockFormat::Compact {
+            features |= FEATURE_COMPACT_BLOCKS;
+        }
+        if let Some(ref mut blob_file) = self.blob_file {
+            blob_file.flush()?;
+            features |= FEATURE_BLOB_VALUES;
+        }
         let index_cont = self.index_block.take().unwrap().finish();
         let ix_handle = self.write_block(index_cont, ctype)?;
 
         // write footer.
-        let footer = Footer::new(meta_ix_handle, ix_handle);
-        let mut buf = [0; FULL_FOOTER_LENGTH];
+        let mut footer = Footer::new(meta_ix_handle, ix_handle);
+        if let Some(digest) = self.file_digest.take() {
+            features |= FEATURE_FILE_CHECKSUM;
+            footer.file_checksum = Some((self.offset, digest.finalize() as u64));
+        }
+        footer.features = features;
+        footer.checksum_type = self.opt.checksum_type;
+        // Tables that LevelDB can read keep its footer.
+        if features != 0 || footer.checksum_type != ChecksumType::Crc32c {
+            footer.format_version = FORMAT_VERSION;
+        }
+        let mut buf = [0; MAX_FOOTER_LENGTH];
+        let footer_len = footer.encoded_length();
         footer.encode(&mut buf);
 
-        self.offset += self.dst.write(&buf[..])?;
+        self.dst.write_all(&buf[..footer_len])?;
+        self.offset += footer_len;
         self.dst.flush()?;
         Ok(self.offset)
     }
@@ -285,13 +762,104 @@ mod tests {
         let mut buf = [0; 48];
         f.encode(&mut buf[..]);
 
-        let f2 = Footer::decode(&buf);
+        let f2 = Footer::decode(&buf).unwrap();
+        assert_eq!(f2.features, 0);
         assert_eq!(f2.meta_index.offset(), 44);
         assert_eq!(f2.meta_index.size(), 4);
         assert_eq!(f2.index.offset(), 55);
         assert_eq!(f2.index.size(), 5);
     }
 
+    #[test]
+    fn test_footer_versioned() {
+        let mut f = Footer::new(BlockHandle::new(44, 4), BlockHandle::new(55, 5));
+        f.format_version = FORMAT_VERSION;
+        f.features = FEATURE_FILE_CHECKSUM | FEATURE_COMPACT_BLOCKS | FEATURE_PARTITIONED_INDEX;
+        f.file_checksum = Some((1234, 0xdeadbeef));
+        f.checksum_type = ChecksumType::Xxh3;
+        assert_eq!(f.encoded_length(), VERSIONED_FOOTER_LENGTH);
+        let mut buf = [0; 80];
+        f.encode(&mut buf[80 - VERSIONED_FOOTER_LENGTH..]);
+
+        let f2 = Footer::decode(&buf).unwrap();
+        assert_eq!(f2.format_version, FORMAT_VERSION);
+        assert_eq!(f2.features, f.features);
+        assert_eq!(f2.file_checksum, Some((1234, 0xdeadbeef)));
+        assert_eq!(f2.checksum_type, ChecksumType::Xxh3);
+        assert_eq!(f2.meta_index.offset(), 44);
+        assert_eq!(f2.index.size(), 5);
+        assert_eq!(
+            f2.feature_names(),
+            vec!["partitioned_index", "compact_blocks", "file_checksum"]
+        );
+        assert_eq!(
+            Footer::decode(&buf[80 - VERSIONED_FOOTER_LENGTH + 4..])
+                .unwrap_err()
+                .code,
+            StatusCode::Corruption
+        );
+
+        // Only RocksDB tables may lack block checksums.
+        let mut no_checksum = buf;
+        0u32.encode_fixed(&mut no_checksum[80 - 20..80 - 16]);
+        assert_eq!(
+            Footer::decode(&no_checksum).unwrap_err().code,
+            StatusCode::Corruption
+        );
+
+        // Unknown format versions are rejected before looking at the rest of the footer.
+        (FORMAT_VERSION + 1).encode_fixed(&mut buf[80 - 12..80 - 8]);
+        for from in [&buf[..], &buf[80 - FULL_FOOTER_LENGTH..]] {
+            assert_eq!(
+                Footer::decode(from).unwrap_err().code,
+                StatusCode::NotSupported
+            );
+        }
+    }
+
+    #[test]
+    fn test_footer_rocksdb() {
+        let mut f = Footer::new(BlockHandle::new(300, 4), BlockHandle::new(55, 5));
+        f.rocksdb_format_version = Some(5);
+        f.checksum_type = ChecksumType::Xxh3;
+        assert_eq!(f.encoded_length(), ROCKSDB_FOOTER_LENGTH);
+        let mut buf = [0; 64];
+        f.encode(&mut buf[64 - ROCKSDB_FOOTER_LENGTH..]);
+
+        // As written by RocksDB.
+        let mut want = vec![4, 0xac, 0x02, 0x04, 0x37, 0x05];
+        want.resize(41, 0);
+        want.extend_from_slice(&[5, 0, 0, 0, 0xf7, 0xcf, 0xf4, 0x85, 0xb7, 0x41, 0xe2, 0x88]);
+        assert_eq!(&buf[64 - ROCKSDB_FOOTER_LENGTH..], want.as_slice());
+
+        let f2 = Footer::decode(&buf).unwrap();
+        assert_eq!(f2.rocksdb_format_version, Some(5));
+        assert_eq!(f2.format_version, FORMAT_VERSION_LEVELDB);
+        assert_eq!(f2.checksum_type, ChecksumType::Xxh3);
+        assert_eq!(f2.meta_index.offset(), 300);
+        assert_eq!(f2.index.size(), 5);
+
+        let mut no_checksum = buf;
+        no_checksum[64 - ROCKSDB_FOOTER_LENGTH] = 0;
+        assert_eq!(
+            Footer::decode(&no_checksum).unwrap().checksum_type,
+            ChecksumType::NoChecksum
+        );
+
+        let mut bad_ctype = buf;
+        bad_ctype[64 - ROCKSDB_FOOTER_LENGTH] = 9;
+        for version in [1, 6] {
+            let mut bad_version = buf;
+            version.encode_fixed(&mut bad_version[64 - 12..64 - 8]);
+            for from in [&bad_version[..], &bad_ctype[..]] {
+                assert_eq!(
+                    Footer::decode(from).unwrap_err().code,
+                    StatusCode::NotSupported
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_table_builder() {
         let mut d = Vec::with_capacity(512);
@@ -300,13 +868,13 @@ mod tests {
         opt.compression_type = CompressionType::CompressionSnappy;
         let mut b = TableBuilder::new(opt, &mut d);
 
-        let data = vec![
+        let data = [
             ("abc", "def"),
             ("abe", "dee"),
             ("bcd", "asa"),
             ("dcc", "a00"),
         ];
-        let data2 = vec![
+        let data2 = [
             ("abd", "def"),
             ("abf", "dee"),
             ("ccd", "asa"),
@@ -314,14 +882,13 @@ mod tests {
         ];
 
         for i in 0..data.len() {
-            b.add(&data[i].0.as_bytes(), &data[i].1.as_bytes()).unwrap();
-            b.add(&data2[i].0.as_bytes(), &data2[i].1.as_bytes())
-                .unwrap();
+            b.add(data[i].0.as_bytes(), data[i].1.as_bytes()).unwrap();
+            b.add(data2[i].0.as_bytes(), data2[i].1.as_bytes()).unwrap();
         }
 
         let estimate = b.size_estimate();
 
-        assert_eq!(143, estimate);
+        assert_eq!(171, estimate);
         assert!(b.filter_block.is_some());
 
         let actual = b.finish().unwrap();
@@ -340,7 +907,7 @@ mod tests {
         let mut b = TableBuilder::new(opt, &mut d);
 
         // Test two equal consecutive keys
-        let data = vec![
+        let data = [
             ("abc", "def"),
             ("abc", "dee"),
             ("bcd", "asa"),
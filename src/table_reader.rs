@@ -1,38 +1,176 @@
+use crate::blob;
 use crate::block::{Block, BlockIter};
+use crate::block_builder::BlockBuilder;
 use crate::blockhandle::BlockHandle;
 use crate::cache;
-use crate::error::Result;
+use crate::cmp::{Cmp, DefaultCmp, InternalKeyCmp};
+use crate::error::{err, Result, Status, StatusCode};
+use crate::filter;
 use crate::filter_block::FilterBlockReader;
-use crate::options::Options;
+use crate::key_types::{self, LookupResult, SequenceNumber, ValueType};
+use crate::options::{self, BlockFormat, ChecksumType, CompressionType, Options, ReadOptions};
+use crate::properties::{TableProperties, PROPERTIES_BLOCK_KEY};
+use crate::range_deletions::{RangeDeletions, RANGE_DELETIONS_BLOCK_KEY};
+use crate::rocksdb::{
+    self, RocksDbProperties, ROCKSDB_PROPERTIES_BLOCK_KEY, ROCKSDB_RANGE_DELETIONS_BLOCK_KEY,
+};
 use crate::table_block;
 use crate::table_builder::{self, Footer};
-use crate::types::{current_key_val, RandomAccess, SSIterator};
+use crate::types::{current_key_val, share, RandomAccess, SSIterator, Shared};
 
 use std::cmp::Ordering;
 use std::fs;
 use std::path;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
 use std::sync::Arc;
 
-use integer_encoding::FixedIntWriter;
+use integer_encoding::{FixedIntWriter, VarInt};
+
+/// The size of the reads of `Table::verify_file_checksum()`.
+const FILE_CHECKSUM_CHUNK_SIZE: usize = 64 * 1024;
 
 /// Reads the table footer.
 fn read_footer(f: &dyn RandomAccess, size: usize) -> Result<Footer> {
-    let mut buf = vec![0; table_builder::FULL_FOOTER_LENGTH];
-    f.read_at(size - table_builder::FULL_FOOTER_LENGTH, &mut buf)?;
-    Ok(Footer::decode(&buf))
+    let len = size.min(table_builder::MAX_FOOTER_LENGTH);
+    let mut buf = vec![0; len];
+    f.read_at(size - len, &mut buf)?;
+    Footer::decode(&buf)
+}
+
+/// FilterStats counts how often the filter of a table has been checked by lookups (`Table::get()`)
+/// and with which result.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FilterStats {
+    /// Number of filter checks.
+    pub checks: u64,
+    /// Number of checks for which the filter ruled out the key.
+    pub negatives: u64,
+    /// Number of checks for which the filter didn't rule out the key, but the table didn't
+    /// contain it.
+    pub false_positives: u64,
+}
+
+impl FilterStats {
+    /// Returns the fraction of lookups for missing keys that weren't ruled out by the filter, or
+    /// 0 if there were no such lookups.
+    pub fn false_positive_rate(&self) -> f64 {
+        let missing = self.negatives + self.false_positives;
+        if missing == 0 {
+            0.
+        } else {
+            self.false_positives as f64 / missing as f64
+        }
+    }
+}
+
+/// TableStats describes the layout of a table; see `Table::stats()`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TableStats {
+    /// The format version recorded in the footer (0 for LevelDB tables).
+    pub format_version: u32,
+    /// The RocksDB format version of tables written by RocksDB.
+    pub rocksdb_format_version: Option<u32>,
+    /// Names of the non-LevelDB features used by the table, as recorded in the footer.
+    pub features: Vec<&'static str>,
+    pub checksum_type: ChecksumType,
+    /// Offset and size of the metaindex block.
+    pub metaindex: (usize, usize),
+    /// Offset and size of the (top-level) index block.
+    pub index: (usize, usize),
+    pub data_blocks: usize,
+    /// Number of data blocks stored compressed.
+    pub compressed_data_blocks: usize,
+    /// Total on-disk size of the data blocks, including their trailers.
+    pub data_size: usize,
+    /// Name of the filter policy whose filter is used, and the size of the filter block.
+    pub filter: Option<(&'static str, usize)>,
+    /// Size of the range filter block, if there is one.
+    pub range_filter_size: Option<usize>,
+}
+
+/// BlockInfo describes a data block; see `Table::blocks()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockInfo {
+    pub offset: usize,
+    /// Size of the (possibly compressed) block on disk, excluding its trailer.
+    pub size: usize,
+    pub uncompressed_size: usize,
+    pub compression: CompressionType,
+    pub entries: usize,
+    pub restarts: usize,
+    pub first_key: Vec<u8>,
+    pub last_key: Vec<u8>,
+}
+
+/// MetaBlockInfo describes an entry of the metaindex block; see `Table::meta_blocks()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetaBlockInfo {
+    /// The metaindex key, e.g. "filter.<policy name>".
+    pub name: Vec<u8>,
+    pub offset: usize,
+    pub size: usize,
+}
+
+#[derive(Default)]
+struct FilterCounters {
+    checks: AtomicU64,
+    negatives: AtomicU64,
+    false_positives: AtomicU64,
+}
+
+/// TableRange is a range of keys in a table, as returned by `Table::split()`. Unlike iterators,
+/// it can be sent to other threads.
+#[derive(Clone)]
+pub struct TableRange {
+    table: Table,
+    /// The first key of the range; None for the first range.
+    pub start: Option<Vec<u8>>,
+    /// The first key after the range; None for the last range.
+    pub end: Option<Vec<u8>>,
+}
+
+impl TableRange {
+    /// Returns an iterator over the entries in the range.
+    pub fn iter(&self) -> TableIterator {
+        self.iter_with_options(&ReadOptions::default())
+    }
+
+    pub fn iter_with_options(&self, ropt: &ReadOptions) -> TableIterator {
+        self.table
+            .iter_bounded(self.start.as_deref(), self.end.as_deref(), ropt)
+    }
 }
 
 /// `Table` is used for accessing SSTables.
 #[derive(Clone)]
 pub struct Table {
     file: Arc<Box<dyn RandomAccess>>,
+    size: usize,
     cache_id: cache::CacheID,
 
     opt: Options,
 
     footer: Footer,
-    index_block: Block,
-    filters: Option<FilterBlockReader>,
+    // The index and filter blocks are read when opening the table and kept for its lifetime,
+    // unless `opt.cache_index_and_filter_blocks` is set: then they are read through the block
+    // cache when needed, and only kept here if `opt.pin_index_and_filter_blocks` is set, too.
+    index_block: Shared<Option<Block>>,
+    filter_location: Option<(filter::BoxedFilterPolicy, BlockHandle)>,
+    filters: Shared<Option<FilterBlockReader>>,
+    // The range filter is handled like the filter block.
+    prefix_filter_len: usize,
+    prefix_filter_location: Option<(filter::BoxedFilterPolicy, BlockHandle)>,
+    prefix_filters: Shared<Option<FilterBlockReader>>,
+
+    // Shared by all clones of the table.
+    filter_counters: Arc<FilterCounters>,
+
+    blob_file: Option<Arc<Box<dyn RandomAccess>>>,
+
+    // The properties are handled like the index block.
+    properties_location: Option<BlockHandle>,
+    properties: Shared<Option<TableProperties>>,
+    range_deletions: Arc<RangeDeletions>,
 }
 
 impl Table {
@@ -44,13 +182,117 @@ impl Table {
     }
 
     /// Creates a new table reader.
-    pub fn new(opt: Options, file: Box<dyn RandomAccess>, size: usize) -> Result<Table> {
+    ///
+    /// Tables written by RocksDB (see the `rocksdb` module) can be read, too; their keys are
+    /// internal keys, which are ordered by an `InternalKeyCmp` over `DefaultCmp` instead of
+    /// `opt.cmp`. Their index block is always kept in memory.
+    pub fn new(mut opt: Options, file: Box<dyn RandomAccess>, size: usize) -> Result<Table> {
         let footer = read_footer(file.as_ref(), size)?;
-        let index_block = table_block::read_table_block(opt.clone(), file.as_ref(), &footer.index)?;
+        opt.block_format = if footer.has_feature(table_builder::FEATURE_COMPACT_BLOCKS) {
+            BlockFormat::Compact
+        } else {
+            BlockFormat::Standard
+        };
+        opt.checksum_type = footer.checksum_type;
+        if footer.rocksdb_format_version.is_some() {
+            opt.cmp = Arc::new(Box::new(InternalKeyCmp::new(Arc::new(Box::new(
+                DefaultCmp,
+            )))));
+        }
         let metaindex_block =
-            table_block::read_table_block(opt.clone(), file.as_ref(), &footer.meta_index)?;
+            table_block::read_table_block(opt.clone(), file.as_ref(), &footer.meta_index, true)?;
+        let filter_location = Table::find_filter_block(&metaindex_block, b"filter.", &opt)
+            .map(|(policy, val)| (policy, BlockHandle::decode(&val).0));
+        let (prefix_filter_len, prefix_filter_location) =
+            match Table::find_filter_block(&metaindex_block, b"prefixfilter.", &opt) {
+                Some((policy, val)) => {
+                    let (location, handle_len) = BlockHandle::decode(&val);
+                    match usize::decode_var(&val[handle_len..]) {
+                        Some((len, _)) if len > 0 => (len, Some((policy, location))),
+                        _ => (0, None),
+                    }
+                }
+                None => (0, None),
+            };
+
+        let rocksdb_properties = match footer.rocksdb_format_version {
+            Some(_) => {
+                match Table::find_meta_block(&metaindex_block, ROCKSDB_PROPERTIES_BLOCK_KEY) {
+                    Some(location) => {
+                        let block = table_block::read_table_block(
+                            opt.clone(),
+                            file.as_ref(),
+                            &location,
+                            true,
+                        )?;
+                        Some(RocksDbProperties::decode(&opt, block.contents().to_vec())?)
+                    }
+                    None => Some(RocksDbProperties::default()),
+                }
+            }
+            None => None,
+        };
+        let properties_location = Table::find_meta_block(&metaindex_block, PROPERTIES_BLOCK_KEY);
+        let properties = match properties_location {
+            Some(_) if opt.cache_index_and_filter_blocks => None,
+            Some(ref location) => {
+                let block =
+                    table_block::read_table_block(opt.clone(), file.as_ref(), location, true)?;
+                Some(TableProperties::decode(&opt, block.contents().to_vec()))
+            }
+            None => rocksdb_properties.as_ref().map(|props| props.table.clone()),
+        };
+        let range_deletions_key = match rocksdb_properties {
+            Some(_) => ROCKSDB_RANGE_DELETIONS_BLOCK_KEY,
+            None => RANGE_DELETIONS_BLOCK_KEY,
+        };
+        let range_deletions = match Table::find_meta_block(&metaindex_block, range_deletions_key) {
+            Some(location) => {
+                let block =
+                    table_block::read_table_block(opt.clone(), file.as_ref(), &location, true)?;
+                let contents = block.contents().to_vec();
+                match rocksdb_properties {
+                    Some(_) => rocksdb::convert_range_deletions(&opt, contents)?,
+                    None => RangeDeletions::decode(&opt, contents),
+                }
+            }
+            None => RangeDeletions::default(),
+        };
+
+        let read_filter =
+            |location: &Option<(filter::BoxedFilterPolicy, BlockHandle)>| match location {
+                Some((ref policy, ref location)) => Ok(Some(table_block::read_filter_block(
+                    file.as_ref(),
+                    location,
+                    policy.clone(),
+                )?)),
+                None => Result::Ok(None),
+            };
+        let index_block = match rocksdb_properties {
+            // RocksDB index blocks are converted once, so they can't be read through the cache.
+            Some(ref props) => {
+                let block =
+                    table_block::read_table_block(opt.clone(), file.as_ref(), &footer.index, true)?;
+                let contents = rocksdb::convert_index_block(&opt, &block.contents(), props)?;
+                Some(Block::new(opt.clone(), contents))
+            }
+            None if opt.cache_index_and_filter_blocks => None,
+            None => Some(table_block::read_table_block(
+                opt.clone(),
+                file.as_ref(),
+                &footer.index,
+                true,
+            )?),
+        };
+        let (filters, prefix_filters) = if opt.cache_index_and_filter_blocks {
+            (None, None)
+        } else {
+            (
+                read_filter(&filter_location)?,
+                read_filter(&prefix_filter_location)?,
+            )
+        };
 
-        let filter_block_reader = Table::read_filter_block(&metaindex_block, file.as_ref(), &opt)?;
         let cache_id = {
             let mut block_cache = opt.block_cache.write()?;
             block_cache.new_cache_id()
@@ -58,38 +300,279 @@ impl Table {
 
         Ok(Table {
             file: Arc::new(file),
-            cache_id: cache_id,
-            opt: opt,
-            footer: footer,
-            filters: filter_block_reader,
-            index_block: index_block,
+            size,
+            cache_id,
+            opt,
+            footer,
+            index_block: share(index_block),
+            filter_location,
+            filters: share(filters),
+            prefix_filter_len,
+            prefix_filter_location,
+            prefix_filters: share(prefix_filters),
+            filter_counters: Arc::new(FilterCounters::default()),
+            blob_file: None,
+            properties_location,
+            properties: share(properties),
+            range_deletions: Arc::new(range_deletions),
         })
     }
 
-    fn read_filter_block(
+    /// Creates a new table reader for a table built with `TableBuilder::new_with_blob_file()`,
+    /// whose large values are read from `blob_file`.
+    pub fn new_with_blob_file(
+        opt: Options,
+        file: Box<dyn RandomAccess>,
+        size: usize,
+        blob_file: Box<dyn RandomAccess>,
+    ) -> Result<Table> {
+        let mut table = Table::new(opt, file, size)?;
+        table.blob_file = Some(Arc::new(blob_file));
+        Ok(table)
+    }
+
+    /// Replaces a value stored in the table with the actual value, if the table has tagged
+    /// values (see the `blob` module).
+    fn resolve_value(&self, val: &mut Vec<u8>, ropt: &ReadOptions) -> Result<()> {
+        if !self.footer.has_feature(table_builder::FEATURE_BLOB_VALUES) {
+            return Ok(());
+        }
+        let blob_file = self.blob_file.as_ref().map(|f| f.as_ref().as_ref());
+        blob::resolve(val, blob_file, ropt.verify_checksums)
+    }
+
+    /// Looks up the filter blocks whose metaindex key starts with `key_prefix` (followed by the
+    /// policy name), and returns the metaindex entry of the first one for which a filter policy is
+    /// known, together with that policy. `opt.filter_policy` is preferred over the policies in
+    /// `opt.filter_policies`.
+    fn find_filter_block(
         metaix: &Block,
-        file: &dyn RandomAccess,
+        key_prefix: &[u8],
         options: &Options,
-    ) -> Result<Option<FilterBlockReader>> {
-        // Open filter block for reading
-        let filter_name = format!("filter.{}", options.filter_policy.name())
-            .as_bytes()
-            .to_vec();
-
+    ) -> Option<(filter::BoxedFilterPolicy, Vec<u8>)> {
+        let mut filter_blocks = vec![];
         let mut metaindexiter = metaix.iter();
-        metaindexiter.seek(&filter_name);
+        metaindexiter.seek(key_prefix);
 
-        if let Some((_key, val)) = current_key_val(&metaindexiter) {
+        while let Some((key, val)) = current_key_val(&metaindexiter) {
+            if !key.starts_with(key_prefix) {
+                break;
+            }
             let filter_block_location = BlockHandle::decode(&val).0;
             if filter_block_location.size() > 0 {
-                return Ok(Some(table_block::read_filter_block(
-                    file,
-                    &filter_block_location,
-                    options.filter_policy.clone(),
-                )?));
+                filter_blocks.push((key[key_prefix.len()..].to_vec(), val));
+            }
+            metaindexiter.advance();
+        }
+
+        for policy in std::iter::once(&options.filter_policy).chain(options.filter_policies.iter())
+        {
+            for (name, val) in filter_blocks.iter() {
+                if name.as_slice() == policy.name().as_bytes() {
+                    return Some((policy.clone(), val.clone()));
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the location of the meta block with metaindex key `name`, if the table has one.
+    /// The metaindex block is scanned completely, as its order depends on the table's comparator.
+    fn find_meta_block(metaix: &Block, name: &[u8]) -> Option<BlockHandle> {
+        let mut iter = metaix.iter();
+        while let Some((key, val)) = SSIterator::next(&mut iter) {
+            if key == name {
+                return Some(BlockHandle::decode(&val).0);
+            }
+        }
+        None
+    }
+
+    /// Returns the properties of the table, or None for tables without a properties block (e.g.
+    /// those written by LevelDB, or without `Options::properties_block`). Like the index block, the properties block is read through the
+    /// block cache if `opt.cache_index_and_filter_blocks` is set.
+    pub fn properties(&self) -> Result<Option<TableProperties>> {
+        if let Some(ref props) = *self.properties.read()? {
+            return Ok(Some(props.clone()));
+        }
+        let location = match self.properties_location {
+            Some(ref location) if self.opt.cache_index_and_filter_blocks => location,
+            _ => return Ok(None),
+        };
+
+        let block = self.read_block(location, &ReadOptions::default())?;
+        let props = TableProperties::decode(&self.opt, block.contents().to_vec());
+        if self.opt.pin_index_and_filter_blocks {
+            *self.properties.write()? = Some(props.clone());
+        }
+        Ok(Some(props))
+    }
+
+    /// Checks the length and checksum of the whole file against the ones recorded in the footer
+    /// (see `Options::file_checksum`), reading the file sequentially. Unlike `verify()`, this
+    /// doesn't decode any blocks, and also detects blocks that are missing or in the wrong place.
+    /// The footer itself isn't covered: a corrupted metaindex or index block handle in the footer
+    /// is only detected when the block it points to fails its own checksum.
+    /// Returns a NotSupported error for tables without a file checksum.
+    pub fn verify_file_checksum(&self) -> Result<()> {
+        let (length, checksum) = match self.footer.file_checksum {
+            Some(file_checksum) => file_checksum,
+            None => return err(StatusCode::NotSupported, "table has no file checksum"),
+        };
+        if length + self.footer.encoded_length() != self.size {
+            return err(
+                StatusCode::Corruption,
+                &format!(
+                    "table size is {}, expected {}",
+                    self.size,
+                    length + self.footer.encoded_length()
+                ),
+            );
+        }
+
+        let mut digest = table_builder::CRC32C.digest();
+        let mut buf = vec![0; FILE_CHECKSUM_CHUNK_SIZE.min(length)];
+        let mut offset = 0;
+        while offset < length {
+            let want = (length - offset).min(buf.len());
+            let n = self.file.read_at(offset, &mut buf[..want])?;
+            if n == 0 {
+                return err(StatusCode::Corruption, "unexpected end of table file");
+            }
+            digest.update(&buf[..n]);
+            offset += n;
+        }
+        if digest.finalize() as u64 != checksum {
+            return err(StatusCode::Corruption, "file checksum mismatch");
+        }
+        Ok(())
+    }
+
+    /// Returns the ranges deleted by `TableBuilder::delete_range()`, merged and in key order, e.g.
+    /// for applying them to other tables. In a table with internal keys, the bounds are internal
+    /// keys: the start has the sequence number of the deletion, and the end is the first internal
+    /// key of the end's user key.
+    pub fn range_deletions(&self) -> &[(Vec<u8>, Vec<u8>)] {
+        self.range_deletions.ranges()
+    }
+
+    /// Returns true if `key` is in a range deleted by `TableBuilder::delete_range()`. In a table
+    /// with internal keys, `key` is an internal key, and true is returned if that version of the
+    /// user key is deleted.
+    pub fn is_range_deleted(&self, key: &[u8]) -> bool {
+        self.range_deletions
+            .covering(self.opt.cmp.as_ref().as_ref(), key)
+            .is_some()
+    }
+
+    /// Returns the name of the filter policy whose filter is used by this table, or None if the
+    /// table has no filter that can be used with the configured policies.
+    pub fn filter_name(&self) -> Option<&'static str> {
+        self.filter_location
+            .as_ref()
+            .map(|(policy, _)| policy.name())
+    }
+
+    /// Returns the filter statistics of this table (and all its clones) since it was opened or
+    /// since the last call to `reset_filter_stats()`.
+    pub fn filter_stats(&self) -> FilterStats {
+        FilterStats {
+            checks: self.filter_counters.checks.load(AtomicOrdering::Relaxed),
+            negatives: self.filter_counters.negatives.load(AtomicOrdering::Relaxed),
+            false_positives: self
+                .filter_counters
+                .false_positives
+                .load(AtomicOrdering::Relaxed),
+        }
+    }
+
+    /// Sets all filter statistics to 0.
+    pub fn reset_filter_stats(&self) {
+        self.filter_counters
+            .checks
+            .store(0, AtomicOrdering::Relaxed);
+        self.filter_counters
+            .negatives
+            .store(0, AtomicOrdering::Relaxed);
+        self.filter_counters
+            .false_positives
+            .store(0, AtomicOrdering::Relaxed);
+    }
+
+    /// Checks `key` against the filter for the block at `block_offset`, and counts the check.
+    fn key_may_match(&self, filters: &FilterBlockReader, block_offset: usize, key: &[u8]) -> bool {
+        self.filter_counters
+            .checks
+            .fetch_add(1, AtomicOrdering::Relaxed);
+        let may_match = filters.key_may_match(block_offset, key);
+        if !may_match {
+            self.filter_counters
+                .negatives
+                .fetch_add(1, AtomicOrdering::Relaxed);
+        }
+        may_match
+    }
+
+    /// Returns the index block of this table, reading it through the block cache if it isn't
+    /// held by the table.
+    fn index_block(&self) -> Result<Block> {
+        if let Some(ref block) = *self.index_block.read()? {
+            return Ok(block.clone());
+        }
+
+        let block = self.read_block(&self.footer.index, &ReadOptions::default())?;
+        if self.opt.pin_index_and_filter_blocks {
+            *self.index_block.write()? = Some(block.clone());
+        }
+        Ok(block)
+    }
+
+    /// Returns a reader for the filter block of this table (if there is one), reading it through
+    /// the block cache if it isn't held by the table.
+    fn filter_block(&self) -> Result<Option<FilterBlockReader>> {
+        self.read_filter(&self.filters, &self.filter_location)
+    }
+
+    /// Like `filter_block()`, for the range filter.
+    fn prefix_filter_block(&self) -> Result<Option<FilterBlockReader>> {
+        self.read_filter(&self.prefix_filters, &self.prefix_filter_location)
+    }
+
+    fn read_filter(
+        &self,
+        filters: &Shared<Option<FilterBlockReader>>,
+        location: &Option<(filter::BoxedFilterPolicy, BlockHandle)>,
+    ) -> Result<Option<FilterBlockReader>> {
+        if let Some(ref filters) = *filters.read()? {
+            return Ok(Some(filters.clone()));
+        }
+        let (policy, location) = match *location {
+            Some((ref policy, ref location)) if self.opt.cache_index_and_filter_blocks => {
+                (policy, location)
+            }
+            _ => return Ok(None),
+        };
+
+        // The block cache only holds Blocks; a filter block is cached as a Block wrapping the raw
+        // filter block contents.
+        let cachekey = self.block_cache_handle(location.offset());
+        let contents = {
+            let mut block_cache = self.opt.block_cache.write()?;
+            if let Some(block) = block_cache.get(&cachekey) {
+                block.contents()
+            } else {
+                let buf = table_block::read_bytes(self.file.as_ref().as_ref(), location)?;
+                let block = Block::new(self.opt.clone(), buf);
+                block_cache.insert(&cachekey, block.clone());
+                block.contents()
             }
+        };
+
+        let reader = FilterBlockReader::new(policy.clone(), contents);
+        if self.opt.pin_index_and_filter_blocks {
+            *filters.write()? = Some(reader.clone());
         }
-        Ok(None)
+        Ok(Some(reader))
     }
 
     /// block_cache_handle creates a CacheKey for a block with a given offset to be used in the
@@ -106,8 +589,8 @@ impl Table {
     }
 
     /// Read a block from the current table at `location`, and cache it in the options' block
-    /// cache.
-    fn read_block(&self, location: &BlockHandle) -> Result<Block> {
+    /// cache (unless `ropt.fill_cache` is false).
+    fn read_block(&self, location: &BlockHandle, ropt: &ReadOptions) -> Result<Block> {
         let cachekey = self.block_cache_handle(location.offset());
         let mut block_cache = self.opt.block_cache.write()?;
         if let Some(block) = block_cache.get(&cachekey) {
@@ -115,85 +598,694 @@ impl Table {
         }
 
         // Two times as_ref(): First time to get a ref from Rc<>, then one from Box<>.
-        let b =
-            table_block::read_table_block(self.opt.clone(), self.file.as_ref().as_ref(), location)?;
-
-        // insert a cheap copy (Arc).
-        block_cache.insert(&cachekey, b.clone());
+        let b = table_block::read_table_block(
+            self.opt.clone(),
+            self.file.as_ref().as_ref(),
+            location,
+            ropt.verify_checksums,
+        )?;
+
+        if ropt.fill_cache {
+            // insert a cheap copy (Arc).
+            block_cache.insert(&cachekey, b.clone());
+        }
 
         Ok(b)
     }
 
-    /// Returns the offset of the block that contains `key`.
-    pub fn approx_offset_of(&self, key: &[u8]) -> usize {
-        let mut iter = self.index_block.iter();
+    /// Returns an iterator over the index entries (separator key -> data block handle) of this
+    /// table. With a partitioned index, this iterates over the entries of the index partitions;
+    /// if a partition can't be read, the iterator stops there, and its `status()` returns the
+    /// error.
+    fn index_iter(&self, ropt: &ReadOptions) -> Result<Box<dyn SSIterator>> {
+        let index_block = self.index_block()?;
+        if self
+            .footer
+            .has_feature(table_builder::FEATURE_PARTITIONED_INDEX)
+        {
+            Ok(Box::new(PartitionedIndexIter {
+                table: self.clone(),
+                top_level: index_block.iter(),
+                partition: None,
+                ropt: *ropt,
+                status: None,
+            }))
+        } else {
+            Ok(Box::new(index_block.iter()))
+        }
+    }
 
+    /// Returns the offset of the block that contains `key`.
+    pub fn approx_offset_of(&self, key: &[u8]) -> Result<usize> {
+        let mut iter = self.index_iter(&ReadOptions::default())?;
         iter.seek(key);
 
         if let Some((_, val)) = current_key_val(&iter) {
             let location = BlockHandle::decode(&val).0;
-            return location.offset();
+            return Ok(location.offset());
+        }
+        iter.status()?;
+
+        Ok(self.footer.meta_index.offset())
+    }
+
+    /// Returns the locations of the data blocks that may contain keys in the range [start, end).
+    fn range_blocks(&self, start: &[u8], end: &[u8]) -> Result<Vec<BlockHandle>> {
+        let mut blocks = vec![];
+        if self.opt.cmp.cmp(start, end) != Ordering::Less {
+            return Ok(blocks);
+        }
+        let mut iter = self.index_iter(&ReadOptions::default())?;
+        iter.seek(start);
+        let (mut key, mut val) = (vec![], vec![]);
+        while iter.current(&mut key, &mut val) {
+            blocks.push(BlockHandle::decode(&val).0);
+            // The index key is at least the last key of its block, and less than the first key
+            // of the next block.
+            if self.opt.cmp.cmp(&key, end) != Ordering::Less {
+                break;
+            }
+            iter.advance();
+        }
+        iter.status()?;
+        Ok(blocks)
+    }
+
+    /// Returns the approximate number of bytes occupied by keys in the range [start, end): the
+    /// total size of the data blocks overlapping with the range. As blocks are only read
+    /// completely, this is also about the number of bytes read by iterating over the range.
+    pub fn approx_size(&self, start: &[u8], end: &[u8]) -> Result<usize> {
+        Ok(self
+            .range_blocks(start, end)?
+            .iter()
+            .map(|b| {
+                b.size()
+                    + table_builder::TABLE_BLOCK_COMPRESS_LEN
+                    + table_builder::TABLE_BLOCK_CKSUM_LEN
+            })
+            .sum())
+    }
+
+    /// Returns the approximate number of entries in the data blocks overlapping with the range
+    /// [start, end), based on the average number of entries per byte of data blocks. That average
+    /// is taken from the properties block, or, if the table has none, from the first overlapping
+    /// data block.
+    pub fn approx_entries(&self, start: &[u8], end: &[u8]) -> Result<usize> {
+        let blocks = self.range_blocks(start, end)?;
+        if blocks.is_empty() {
+            return Ok(0);
+        }
+        let size: usize = blocks.iter().map(|b| b.size()).sum();
+
+        let (entries, data_size) = match self.properties()? {
+            Some(ref props) => (
+                props.num_entries,
+                // Excluding the block trailers.
+                props.data_size.saturating_sub(
+                    props.num_data_blocks
+                        * (table_builder::TABLE_BLOCK_COMPRESS_LEN
+                            + table_builder::TABLE_BLOCK_CKSUM_LEN),
+                ),
+            ),
+            None => {
+                let mut iter = self.read_block(&blocks[0], &ReadOptions::default())?.iter();
+                let mut entries = 0;
+                while iter.advance() {
+                    entries += 1;
+                }
+                (entries, blocks[0].size())
+            }
+        };
+        if data_size == 0 {
+            return Ok(0);
+        }
+        Ok((entries as f64 * size as f64 / data_size as f64).round() as usize)
+    }
+
+    /// Returns statistics on the layout of the table. Apart from the index, only the trailers of
+    /// the data blocks are read.
+    pub fn stats(&self) -> Result<TableStats> {
+        let ropt = ReadOptions {
+            fill_cache: false,
+            ..ReadOptions::default()
+        };
+        let mut stats = TableStats {
+            format_version: self.footer.format_version,
+            rocksdb_format_version: self.footer.rocksdb_format_version,
+            features: self.footer.feature_names(),
+            checksum_type: self.footer.checksum_type,
+            metaindex: (
+                self.footer.meta_index.offset(),
+                self.footer.meta_index.size(),
+            ),
+            index: (self.footer.index.offset(), self.footer.index.size()),
+            filter: self
+                .filter_location
+                .as_ref()
+                .map(|(policy, location)| (policy.name(), location.size())),
+            range_filter_size: self
+                .prefix_filter_location
+                .as_ref()
+                .map(|(_, location)| location.size()),
+            ..TableStats::default()
+        };
+
+        let mut index_iter = self.index_iter(&ropt)?;
+        while let Some((_, val)) = SSIterator::next(&mut index_iter) {
+            let location = BlockHandle::decode(&val).0;
+            stats.data_blocks += 1;
+            if self.block_compression(&location)? != CompressionType::CompressionNone {
+                stats.compressed_data_blocks += 1;
+            }
+            stats.data_size += location.size()
+                + table_builder::TABLE_BLOCK_COMPRESS_LEN
+                + table_builder::TABLE_BLOCK_CKSUM_LEN;
+        }
+        index_iter.status()?;
+        Ok(stats)
+    }
+
+    /// Returns the compression type of the block at `location`, as recorded in its trailer.
+    fn block_compression(&self, location: &BlockHandle) -> Result<CompressionType> {
+        let mut compression = [0];
+        self.file
+            .read_at(location.offset() + location.size(), &mut compression)?;
+        match options::int_to_compressiontype(compression[0] as u32) {
+            Some(ctype) => Ok(ctype),
+            None => err(
+                StatusCode::Corruption,
+                &format!("bad compression type in block at {}", location.offset()),
+            ),
+        }
+    }
+
+    /// Returns a description of each data block, in key order. This reads all data blocks
+    /// (without adding them to the block cache).
+    pub fn blocks(&self) -> Result<Vec<BlockInfo>> {
+        let ropt = ReadOptions {
+            fill_cache: false,
+            ..ReadOptions::default()
+        };
+        let mut index_iter = self.index_iter(&ropt)?;
+        let mut blocks = vec![];
+        while let Some((_, val)) = SSIterator::next(&mut index_iter) {
+            let location = BlockHandle::decode(&val).0;
+            let block = self.read_block(&location, &ropt)?;
+            let mut iter = block.iter();
+            let mut info = BlockInfo {
+                offset: location.offset(),
+                size: location.size(),
+                uncompressed_size: block.contents().len(),
+                compression: self.block_compression(&location)?,
+                entries: 0,
+                restarts: iter.number_restarts(),
+                first_key: vec![],
+                last_key: vec![],
+            };
+            while iter.advance() {
+                if info.entries == 0 {
+                    info.first_key = iter.current_key().unwrap_or_default().to_vec();
+                }
+                info.entries += 1;
+            }
+            // seek_to_last() panics on blocks without entries.
+            if info.entries > 0 {
+                iter.seek_to_last();
+                info.last_key = iter.current_key().unwrap_or_default().to_vec();
+            }
+            blocks.push(info);
+        }
+        index_iter.status()?;
+        Ok(blocks)
+    }
+
+    /// Returns the entries of the metaindex block, which point to the meta blocks (e.g. filters).
+    pub fn meta_blocks(&self) -> Result<Vec<MetaBlockInfo>> {
+        let metaindex = table_block::read_table_block(
+            self.opt.clone(),
+            self.file.as_ref().as_ref(),
+            &self.footer.meta_index,
+            true,
+        )?;
+        let mut iter = metaindex.iter();
+        let mut meta_blocks = vec![];
+        while let Some((name, val)) = SSIterator::next(&mut iter) {
+            let location = BlockHandle::decode(&val).0;
+            meta_blocks.push(MetaBlockInfo {
+                name,
+                offset: location.offset(),
+                size: location.size(),
+            });
         }
+        Ok(meta_blocks)
+    }
 
-        return self.footer.meta_index.offset();
+    /// Reads all data blocks, verifies their checksums and checks that their keys are ordered
+    /// correctly; returns the number of entries. Unlike iterators, which skip blocks that can't be
+    /// read, this fails at the first defective block.
+    pub fn verify(&self) -> Result<usize> {
+        let ropt = ReadOptions {
+            fill_cache: false,
+            verify_checksums: true,
+            hide_range_deletions: false,
+        };
+        let mut index_iter = self.index_iter(&ropt)?;
+        let mut entries = 0;
+        let (mut key, mut val) = (vec![], vec![]);
+        let mut last_key: Option<Vec<u8>> = None;
+        while let Some((index_key, handle)) = SSIterator::next(&mut index_iter) {
+            let location = BlockHandle::decode(&handle).0;
+            let block = table_block::read_table_block(
+                self.opt.clone(),
+                self.file.as_ref().as_ref(),
+                &location,
+                true,
+            )?;
+            let mut iter = block.iter();
+            while iter.advance() {
+                iter.current(&mut key, &mut val);
+                if let Some(ref last) = last_key {
+                    if self.opt.cmp.cmp(last, &key) != Ordering::Less {
+                        return err(
+                            StatusCode::Corruption,
+                            &format!("keys out of order in block at {}", location.offset()),
+                        );
+                    }
+                }
+                entries += 1;
+                last_key = Some(key.clone());
+            }
+            if let Some(ref last) = last_key {
+                if self.opt.cmp.cmp(last, &index_key) == Ordering::Greater {
+                    return err(
+                        StatusCode::Corruption,
+                        &format!(
+                            "index key is smaller than the keys in block at {}",
+                            location.offset()
+                        ),
+                    );
+                }
+            }
+        }
+        index_iter.status()?;
+        Ok(entries)
     }
 
     /// Returns an iterator over an SSTable. Iterators hold internal references to the table, so
-    /// make sure to let them expire when not needed anymore.
+    /// make sure to let them expire when not needed anymore. Unlike `get()`, the iterator returns
+    /// keys in a range deleted by `TableBuilder::delete_range()`; see
+    /// `ReadOptions::hide_range_deletions`.
     pub fn iter(&self) -> TableIterator {
-        let iter = TableIterator {
+        self.iter_with_options(&ReadOptions::default())
+    }
+
+    /// Like `iter()`, but the returned iterator reads blocks according to `ropt`. For example,
+    /// a full scan should set `ropt.fill_cache = false` in order to not evict the working set
+    /// from the block cache.
+    /// If the index block can't be read, the iterator is empty, and `TableIterator::status()`
+    /// returns the error. Data blocks that can't be read are skipped; `status()` returns the error
+    /// from then on, while the iterator continues with the next block.
+    pub fn iter_with_options(&self, ropt: &ReadOptions) -> TableIterator {
+        let (index_iter, status) = match self.index_iter(ropt) {
+            Ok(index_iter) => (index_iter, None),
+            Err(e) => {
+                let empty = Block::new(
+                    self.opt.clone(),
+                    BlockBuilder::new(self.opt.clone()).finish(),
+                );
+                (Box::new(empty.iter()) as Box<dyn SSIterator>, Some(e))
+            }
+        };
+        TableIterator {
             current_block: None,
             current_block_off: 0,
-            index_block: self.index_block.iter(),
+            index_block: index_iter,
             table: self.clone(),
-        };
+            ropt: *ropt,
+            lower: None,
+            upper: None,
+            range_filter: None,
+            value: None,
+            status,
+            block_error: None,
+        }
+    }
+
+    /// Returns an iterator over the entries with keys in the range [start, end). If the table has
+    /// a range filter and `start` and `end` share a prefix of the filter's prefix length, data
+    /// blocks that contain no key with that prefix are skipped.
+    pub fn iter_range(&self, start: &[u8], end: &[u8]) -> TableIterator {
+        self.iter_range_with_options(start, end, &ReadOptions::default())
+    }
+
+    /// Like `iter_range()`, but the returned iterator reads blocks according to `ropt`.
+    pub fn iter_range_with_options(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        ropt: &ReadOptions,
+    ) -> TableIterator {
+        let mut iter = self.iter_bounded(Some(start), Some(end), ropt);
+        // Without range filter, the iterator just doesn't skip any blocks.
+        iter.range_filter = self.range_filter(start, end).unwrap_or(None);
         iter
     }
 
+    /// Returns an iterator over the entries with keys that are at least `lower` and less than
+    /// `upper`, where a missing bound doesn't restrict the iterator.
+    fn iter_bounded(
+        &self,
+        lower: Option<&[u8]>,
+        upper: Option<&[u8]>,
+        ropt: &ReadOptions,
+    ) -> TableIterator {
+        let mut iter = self.iter_with_options(ropt);
+        iter.lower = lower.map(|k| k.to_vec());
+        iter.upper = upper.map(|k| k.to_vec());
+        iter
+    }
+
+    /// Returns up to `n - 1` keys that divide the table into `n` ranges of about the same size.
+    /// The keys are taken from the index, so that each range consists of whole data blocks;
+    /// fewer keys are returned if the table has fewer than `n` data blocks.
+    pub fn split_points(&self, n: usize) -> Result<Vec<Vec<u8>>> {
+        let ropt = ReadOptions {
+            fill_cache: false,
+            ..ReadOptions::default()
+        };
+        let mut index_iter = self.index_iter(&ropt)?;
+        let mut blocks = vec![];
+        while let Some((key, val)) = SSIterator::next(&mut index_iter) {
+            blocks.push((key, BlockHandle::decode(&val).0.size()));
+        }
+        index_iter.status()?;
+        let total: usize = blocks.iter().map(|(_, size)| size).sum();
+
+        let mut points = vec![];
+        let mut size = 0;
+        // Splitting after the last block would result in an empty range.
+        for (key, block_size) in blocks.into_iter().rev().skip(1).rev() {
+            size += block_size;
+            if points.len() + 1 < n && size * n >= total * (points.len() + 1) {
+                points.push(key);
+            }
+        }
+        Ok(points)
+    }
+
+    /// Splits the table into up to `n` ranges of about the same size (see `split_points()`),
+    /// which can be iterated over independently, e.g. on different threads.
+    pub fn split(&self, n: usize) -> Result<Vec<TableRange>> {
+        let points = self.split_points(n)?;
+        let mut ranges = Vec::with_capacity(points.len() + 1);
+        let mut start = None;
+        for point in points.into_iter().map(Some).chain(std::iter::once(None)) {
+            ranges.push(TableRange {
+                table: self.clone(),
+                start: start.take(),
+                end: point.clone(),
+            });
+            start = point;
+        }
+        Ok(ranges)
+    }
+
+    /// Returns the prefix shared by all keys in [start, end) that can be looked up in the range
+    /// filter, together with the range filter -- or None if the range filter can't be used for
+    /// this range.
+    fn range_filter(
+        &self,
+        start: &[u8],
+        end: &[u8],
+    ) -> Result<Option<(Vec<u8>, FilterBlockReader)>> {
+        let len = self.prefix_filter_len;
+        // Only with a bytewise ordering, all keys between two keys with the same prefix have that
+        // prefix, too.
+        if self.prefix_filter_location.is_none()
+            || self.opt.cmp.id() != DefaultCmp.id()
+            || start.len() < len
+            || end.len() < len
+            || start[..len] != end[..len]
+        {
+            return Ok(None);
+        }
+        Ok(self
+            .prefix_filter_block()?
+            .map(|filters| (start[..len].to_vec(), filters)))
+    }
+
+    /// Returns false if the table provably contains no key in the range [start, end). This uses
+    /// the range filter if there is one (see `Options::range_filter_prefix_len`) and `start` and
+    /// `end` share a prefix of the filter's prefix length; otherwise, only ranges past the last
+    /// key of the table are ruled out.
+    pub fn may_contain_range(&self, start: &[u8], end: &[u8]) -> Result<bool> {
+        if self.opt.cmp.cmp(start, end) != Ordering::Less {
+            return Ok(false);
+        }
+        let range_filter = self.range_filter(start, end)?;
+
+        let mut index_iter = self.index_iter(&ReadOptions::default())?;
+        index_iter.seek(start);
+        while let Some((last_in_block, h)) = current_key_val(index_iter.as_ref()) {
+            let handle = BlockHandle::decode(&h).0;
+            match range_filter {
+                Some((ref prefix, ref filters)) => {
+                    if filters.key_may_match(handle.offset(), prefix) {
+                        return Ok(true);
+                    }
+                }
+                None => return Ok(true),
+            }
+            // The following blocks only contain keys past `end`.
+            if self.opt.cmp.cmp(&last_in_block, end) != Ordering::Less {
+                break;
+            }
+            index_iter.advance();
+        }
+        index_iter.status()?;
+        Ok(false)
+    }
+
     /// Retrieve an entry for a key from the table. This function uses the attached filters, so
     /// is better suited if you frequently look for non-existing values (as it will detect the
-    /// non-existence of an entry in a block without having to load the block).
+    /// non-existence of an entry in a block without having to load the block). Keys in a range
+    /// deleted by `TableBuilder::delete_range()` are not found.
     pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
-        let mut index_iter = self.index_block.iter();
+        self.get_with_options(key, &ReadOptions::default())
+    }
+
+    /// Like `get()`, but the data block is read according to `ropt`. Keys in a deleted range are
+    /// not found even if `ropt.hide_range_deletions` isn't set.
+    pub fn get_with_options(&self, key: &[u8], ropt: &ReadOptions) -> Result<Option<Vec<u8>>> {
+        if self.is_range_deleted(key) {
+            return Ok(None);
+        }
+        let filters = self.filter_block()?;
+        let (mut val, filter_passed) = self.get_filtered(key, ropt, filters.as_ref())?;
+        match val {
+            Some(ref mut val) => self.resolve_value(val, ropt)?,
+            None if filter_passed => {
+                self.filter_counters
+                    .false_positives
+                    .fetch_add(1, AtomicOrdering::Relaxed);
+            }
+            None => {}
+        }
+        Ok(val)
+    }
+
+    /// Looks up `key` using `filters`. Also returns whether a filter was checked and didn't rule
+    /// out the key.
+    fn get_filtered(
+        &self,
+        key: &[u8],
+        ropt: &ReadOptions,
+        filters: Option<&FilterBlockReader>,
+    ) -> Result<(Option<Vec<u8>>, bool)> {
+        let mut filter_passed = false;
+        // A full filter can be checked before looking at the index.
+        if let Some(filters) = filters {
+            if filters.is_full() {
+                if !self.key_may_match(filters, 0, key) {
+                    return Ok((None, filter_passed));
+                }
+                filter_passed = true;
+            }
+        }
+
+        let mut index_iter = self.index_iter(ropt)?;
         index_iter.seek(key);
 
         let handle;
-        if let Some((last_in_block, h)) = current_key_val(&index_iter) {
-            if self.opt.cmp.cmp(key, &last_in_block) == Ordering::Less {
+        if let Some((last_in_block, h)) = current_key_val(index_iter.as_ref()) {
+            // With internal keys, the index key may be equal to the last key in the block.
+            if self.opt.cmp.cmp(key, &last_in_block) != Ordering::Greater {
                 handle = BlockHandle::decode(&h).0;
             } else {
-                return Ok(None);
+                return Ok((None, filter_passed));
             }
         } else {
-            return Ok(None);
+            index_iter.status()?;
+            return Ok((None, filter_passed));
         }
 
         // found correct block.
 
         // Check bloom (or whatever) filter
-        if let Some(ref filters) = self.filters {
-            if !filters.key_may_match(handle.offset(), key) {
-                return Ok(None);
+        if let Some(filters) = filters {
+            if !filters.is_full() {
+                if !self.key_may_match(filters, handle.offset(), key) {
+                    return Ok((None, filter_passed));
+                }
+                filter_passed = true;
             }
         }
 
         // Read block (potentially from cache)
-        let tb = self.read_block(&handle)?;
+        let tb = self.read_block(&handle, ropt)?;
         let mut iter = tb.iter();
 
-        // Go to entry and check if it's the wanted entry.
-        iter.seek(key);
+        // Go to entry and check if it's the wanted entry. Only the hash indexes of this crate can
+        // be used; RocksDB's data block hash indexes use a different hash function.
+        if !self
+            .footer
+            .has_feature(table_builder::FEATURE_DATA_BLOCK_HASH_INDEX)
+        {
+            iter.seek(key);
+        } else if !iter.seek_for_get(key) {
+            return Ok((None, filter_passed));
+        }
         if let Some((k, v)) = current_key_val(&iter) {
             if self.opt.cmp.cmp(&k, key) == Ordering::Equal {
-                return Ok(Some(v));
+                // The values of RocksDB entries that can't be read must not be returned as is.
+                if self.footer.rocksdb_format_version.is_some() {
+                    rocksdb::parse_internal_key(&k)?;
+                }
+                return Ok((Some(v), filter_passed));
+            }
+        }
+        Ok((None, filter_passed))
+    }
+}
+
+impl Table {
+    /// Looks up the newest version of `key` with a sequence number of at most `seq` in a table
+    /// with internal keys (see `Options::with_internal_keys()`). Returns `LookupResult::Deleted`
+    /// if that version is a deletion, or if a range deletion visible at `seq` deletes it (or any
+    /// version of `key`, if there is none); and None if there is no such version.
+    pub fn get_at(&self, key: &[u8], seq: SequenceNumber) -> Result<Option<LookupResult>> {
+        self.get_at_with_options(key, seq, &ReadOptions::default())
+    }
+
+    /// Like `get_at()`, but data blocks are read according to `ropt`. Range deletions are applied
+    /// even if `ropt.hide_range_deletions` isn't set.
+    pub fn get_at_with_options(
+        &self,
+        key: &[u8],
+        seq: SequenceNumber,
+        ropt: &ReadOptions,
+    ) -> Result<Option<LookupResult>> {
+        let filters = self.filter_block()?;
+        let (result, filter_passed) = self.get_at_filtered(key, seq, ropt, filters.as_ref())?;
+        if result.is_none() && filter_passed {
+            self.filter_counters
+                .false_positives
+                .fetch_add(1, AtomicOrdering::Relaxed);
+        }
+        let range_seq =
+            self.range_deletions
+                .newest_covering(self.opt.cmp.as_ref().as_ref(), key, seq);
+        let version = match result {
+            Some(LookupResult::Value { seq, .. }) | Some(LookupResult::Deleted { seq }) => {
+                Some(seq)
+            }
+            None => None,
+        };
+        match range_seq {
+            // The range deletion is newer than the version found, if any.
+            Some(range_seq) if version.is_none_or(|version| version < range_seq) => {
+                Ok(Some(LookupResult::Deleted { seq: range_seq }))
+            }
+            _ => Ok(result),
+        }
+    }
+
+    /// Like `get_filtered()`, for `get_at()`.
+    fn get_at_filtered(
+        &self,
+        key: &[u8],
+        seq: SequenceNumber,
+        ropt: &ReadOptions,
+        filters: Option<&FilterBlockReader>,
+    ) -> Result<(Option<LookupResult>, bool)> {
+        let mut filter_passed = false;
+        // The versions of `key` visible at `seq` are in [lookup, last].
+        let lookup = key_types::build_internal_key(key, seq, ValueType::TypeValue);
+        let last = key_types::build_internal_key(key, 0, ValueType::TypeDeletion);
+
+        if let Some(filters) = filters {
+            if filters.is_full() {
+                if !self.key_may_match(filters, 0, &lookup) {
+                    return Ok((None, filter_passed));
+                }
+                filter_passed = true;
+            }
+        }
+
+        let mut index_iter = self.index_iter(ropt)?;
+        index_iter.seek(&lookup);
+        // The versions of a key may span several blocks, but the filters of all of them match the
+        // key; so the newest visible version is in the first block that has any entry in range.
+        while let Some((last_in_block, h)) = current_key_val(index_iter.as_ref()) {
+            let handle = BlockHandle::decode(&h).0;
+
+            let may_match = match filters {
+                Some(filters) if !filters.is_full() => {
+                    let may_match = self.key_may_match(filters, handle.offset(), &lookup);
+                    filter_passed |= may_match;
+                    may_match
+                }
+                _ => true,
+            };
+            if may_match {
+                let block = self.read_block(&handle, ropt)?;
+                let mut iter = block.iter();
+                // Not `seek_for_get()`: a hash index only finds exact keys.
+                iter.seek(&lookup);
+                if let Some((k, mut v)) = current_key_val(&iter) {
+                    if self.opt.cmp.cmp(&k, &last) == Ordering::Greater {
+                        return Ok((None, filter_passed));
+                    }
+                    let parsed = match self.footer.rocksdb_format_version {
+                        Some(_) => Some(rocksdb::parse_internal_key(&k)?),
+                        None => key_types::parse_internal_key(&k),
+                    };
+                    let result = match parsed {
+                        Some((_, seq, ValueType::TypeValue)) => {
+                            self.resolve_value(&mut v, ropt)?;
+                            LookupResult::Value { seq, value: v }
+                        }
+                        Some((_, seq, ValueType::TypeDeletion)) => LookupResult::Deleted { seq },
+                        None => {
+                            return err(StatusCode::Corruption, "invalid internal key in table")
+                        }
+                    };
+                    return Ok((Some(result), filter_passed));
+                }
             }
+            // The following blocks only contain later keys.
+            if self.opt.cmp.cmp(&last_in_block, &last) != Ordering::Less {
+                return Ok((None, filter_passed));
+            }
+            index_iter.advance();
         }
-        Ok(None)
+        index_iter.status()?;
+        Ok((None, filter_passed))
     }
 }
 
 /// This iterator is a "TwoLevelIterator"; it uses an index block in order to get an offset hint
-/// into the data blocks.
+/// into the data blocks. (Tables with a partitioned index use a PartitionedIndexIter as index.)
 pub struct TableIterator {
     // A TableIterator is independent of its table (on the syntax level -- it doesn't know its
     // Table's lifetime). This is mainly required by the dynamic iterators used everywhere, where a
@@ -204,7 +1296,21 @@ pub struct TableIterator {
     table: Table,
     current_block: Option<BlockIter>,
     current_block_off: usize,
-    index_block: BlockIter,
+    index_block: Box<dyn SSIterator>,
+    ropt: ReadOptions,
+
+    // Bounds of a range iterator: [lower, upper).
+    lower: Option<Vec<u8>>,
+    upper: Option<Vec<u8>>,
+    // The prefix of all keys in range, and the range filter used to skip blocks without it.
+    range_filter: Option<(Vec<u8>, FilterBlockReader)>,
+    // The resolved value of the current entry, if values are resolved and the table has
+    // FEATURE_BLOB_VALUES.
+    value: Option<Vec<u8>>,
+    // The error that made the iterator invalid, e.g. an index block that couldn't be read.
+    status: Option<Status>,
+    // The error of the last data block that couldn't be read and was skipped.
+    block_error: Option<Status>,
 }
 
 impl TableIterator {
@@ -213,250 +1319,2111 @@ impl TableIterator {
     // Err means corruption or I/O error; Ok(true) means a new block was loaded; Ok(false) means
     // tht there's no more entries.
     fn skip_to_next_entry(&mut self) -> Result<bool> {
-        if let Some((_key, val)) = self.index_block.next() {
-            self.load_block(&val).map(|_| true)
-        } else {
-            Ok(false)
+        while let Some((key, val)) = SSIterator::next(&mut self.index_block) {
+            if self.block_may_contain_range(&val) {
+                return self.load_block(&val).map(|_| true);
+            }
+            // The following blocks only contain keys past the upper bound.
+            if !self.below_upper_bound(&key) {
+                break;
+            }
         }
+        Ok(false)
     }
 
-    // Load the block at `handle` into `self.current_block`
-    fn load_block(&mut self, handle: &[u8]) -> Result<()> {
-        let (new_block_handle, _) = BlockHandle::decode(handle);
-        let block = self.table.read_block(&new_block_handle)?;
+    // Returns false if the range filter rules out the block at `handle`.
+    fn block_may_contain_range(&self, handle: &[u8]) -> bool {
+        match self.range_filter {
+            Some((ref prefix, ref filters)) => {
+                filters.key_may_match(BlockHandle::decode(handle).0.offset(), prefix)
+            }
+            None => true,
+        }
+    }
+
+    fn below_upper_bound(&self, key: &[u8]) -> bool {
+        match self.upper {
+            Some(ref upper) => self.table.opt.cmp.cmp(key, upper) == Ordering::Less,
+            None => true,
+        }
+    }
+
+    // Resets the iterator if its current entry is past the upper bound. Returns whether the
+    // iterator is (still) valid.
+    fn check_upper_bound(&mut self) -> bool {
+        match self.current_key() {
+            Some(key) if !self.below_upper_bound(key) => {}
+            Some(_) => return true,
+            None => return false,
+        }
+        self.reset();
+        false
+    }
+
+    // Resets the iterator if its current entry is before the lower bound. Returns whether the
+    // iterator is (still) valid.
+    fn check_lower_bound(&mut self) -> bool {
+        match (self.current_key(), self.lower.as_ref()) {
+            (Some(key), Some(lower)) if self.table.opt.cmp.cmp(key, lower) == Ordering::Less => {}
+            (Some(_), _) => return true,
+            (None, _) => return false,
+        }
+        self.reset();
+        false
+    }
+
+    // Fails on entries of RocksDB tables whose value type can't be read (see
+    // `rocksdb::parse_internal_key()`): the iterator becomes invalid, and status() returns the
+    // error. Returns whether the iterator is (still) valid.
+    fn check_rocksdb_key(&mut self) -> bool {
+        if self.table.footer.rocksdb_format_version.is_none() {
+            return self.valid();
+        }
+        match self.current_key().map(rocksdb::parse_internal_key) {
+            Some(Ok(_)) => true,
+            Some(Err(e)) => {
+                self.status = Some(e);
+                self.reset();
+                false
+            }
+            None => false,
+        }
+    }
+
+    // Resolves the value of the current entry once it is positioned, so that current() doesn't
+    // read the blob file again. If that fails, the iterator becomes invalid, and status() returns
+    // the error. Returns whether the iterator is (still) valid.
+    fn resolve_current_value(&mut self) -> bool {
+        self.value = None;
+        if !self
+            .table
+            .footer
+            .has_feature(table_builder::FEATURE_BLOB_VALUES)
+        {
+            return self.valid();
+        }
+        let (mut key, mut val) = (vec![], vec![]);
+        match self.current_block {
+            Some(ref cb) if cb.current(&mut key, &mut val) => {}
+            _ => return false,
+        }
+        match self.table.resolve_value(&mut val, &self.ropt) {
+            Ok(()) => {
+                self.value = Some(val);
+                true
+            }
+            Err(e) => {
+                self.status = Some(e);
+                self.reset();
+                false
+            }
+        }
+    }
+
+    // Load the block at `handle` into `self.current_block`
+    fn load_block(&mut self, handle: &[u8]) -> Result<()> {
+        let (new_block_handle, _) = BlockHandle::decode(handle);
+        let block = self.table.read_block(&new_block_handle, &self.ropt)?;
+
+        self.current_block = Some(block.iter());
+        self.current_block_off = new_block_handle.offset();
+
+        Ok(())
+    }
+}
+
+// The positioning methods without regard to range deletions.
+impl TableIterator {
+    fn next_entry(&mut self) -> bool {
+        // Uninitialized case.
+        if self.current_block.is_none() {
+            // A range iterator starts at its lower bound.
+            if !self.index_block.valid() {
+                if let Some(lower) = self.lower.clone() {
+                    self.seek_entry(&lower);
+                    return self.valid();
+                }
+            }
+            match self.skip_to_next_entry() {
+                Ok(true) => return self.next_entry(),
+                Ok(false) => {
+                    self.reset();
+                    return false;
+                }
+                // try next block from index, this might be corruption
+                Err(e) => {
+                    self.block_error = Some(e);
+                    return self.next_entry();
+                }
+            }
+        }
+
+        // Initialized case -- does the current block have more entries?
+        if let Some(ref mut cb) = self.current_block {
+            if cb.advance() {
+                return self.check_upper_bound();
+            }
+        }
+
+        // If the current block is exhausted, try loading the next block.
+        self.current_block = None;
+        match self.skip_to_next_entry() {
+            Ok(true) => self.next_entry(),
+            Ok(false) => {
+                self.reset();
+                false
+            }
+            // try next block, this might be corruption
+            Err(e) => {
+                self.block_error = Some(e);
+                self.next_entry()
+            }
+        }
+    }
+
+    fn seek_entry(&mut self, to: &[u8]) {
+        let lower;
+        let to = match self.lower {
+            Some(ref l) if self.table.opt.cmp.cmp(to, l) == Ordering::Less => {
+                lower = l.clone();
+                &lower
+            }
+            _ => to,
+        };
+
+        // first seek in index block, rewind by one entry (so we get the next smaller index entry),
+        // then set current_block and seek there
+        self.index_block.seek(to);
+
+        // It's possible that this is a seek past-last; reset in that case.
+        if let Some((past_block, handle)) = current_key_val(self.index_block.as_ref()) {
+            if self.table.opt.cmp.cmp(to, &past_block) <= Ordering::Equal {
+                // ok, found right block: continue
+                if !self.block_may_contain_range(&handle) {
+                    self.current_block = None;
+                    self.next_entry();
+                    return;
+                }
+                match self.load_block(&handle) {
+                    Ok(()) => {
+                        // current_block is always set if load_block() returned Ok.
+                        let cb = self.current_block.as_mut().unwrap();
+                        cb.seek(to);
+                        // `to` may be between the last key of the block and the separator key in
+                        // the index; the next entry is the first one of the next block then.
+                        if !cb.valid() {
+                            self.current_block = None;
+                            self.next_entry();
+                            return;
+                        }
+                        self.check_upper_bound();
+                        return;
+                    }
+                    Err(e) => self.block_error = Some(e),
+                }
+            }
+        }
+        // Reached in case of failure.
+        self.reset();
+    }
+
+    fn prev_entry(&mut self) -> bool {
+        // happy path: current block contains previous entry
+        if let Some(ref mut cb) = self.current_block {
+            if cb.prev() {
+                return self.check_lower_bound();
+            }
+        }
+
+        // Go back one block and look for the last entry in the previous block
+        if self.index_block.prev() {
+            if let Some((_, handle)) = current_key_val(self.index_block.as_ref()) {
+                match self.load_block(&handle) {
+                    Ok(()) => {
+                        self.current_block.as_mut().unwrap().seek_to_last();
+                        self.check_lower_bound()
+                    }
+                    Err(e) => {
+                        self.block_error = Some(e);
+                        self.reset();
+                        false
+                    }
+                }
+            } else {
+                false
+            }
+        } else {
+            false
+        }
+    }
+
+    // Returns the range deletion covering the current entry, if range deletions are hidden.
+    fn covering_range_deletion(&self) -> Option<(Vec<u8>, Vec<u8>)> {
+        if !self.ropt.hide_range_deletions || self.table.range_deletions.is_empty() {
+            return None;
+        }
+        let key = self.current_key()?;
+        self.table
+            .range_deletions
+            .covering(self.table.opt.cmp.as_ref().as_ref(), key)
+            .cloned()
+    }
+
+    // Moves forward to the first entry not covered by a range deletion.
+    fn skip_range_deletions_forward(&mut self) -> bool {
+        while let Some((_, end)) = self.covering_range_deletion() {
+            // With internal keys, newer versions of the keys in range aren't deleted.
+            if self.table.range_deletions.has_internal_keys() {
+                self.next_entry();
+            } else {
+                self.seek_entry(&end);
+            }
+        }
+        self.valid()
+    }
+
+    // Moves backward to the last entry not covered by a range deletion.
+    fn skip_range_deletions_backward(&mut self) -> bool {
+        while let Some((start, _)) = self.covering_range_deletion() {
+            // Without internal keys, the first entry at or after `start` is covered, and the one
+            // before it is not.
+            if !self.table.range_deletions.has_internal_keys() {
+                self.seek_entry(&start);
+            }
+            if !self.valid() || !self.prev_entry() {
+                self.reset();
+                return false;
+            }
+        }
+        self.valid()
+    }
+}
+
+// Once an error is recorded in `status`, the iterator stays invalid.
+impl SSIterator for TableIterator {
+    fn advance(&mut self) -> bool {
+        if self.status.is_some() {
+            return false;
+        }
+        self.next_entry();
+        self.skip_range_deletions_forward()
+            && self.check_rocksdb_key()
+            && self.resolve_current_value()
+    }
+
+    // A call to valid() after seeking is necessary to ensure that the seek worked (e.g., no error
+    // while reading from disk)
+    fn seek(&mut self, to: &[u8]) {
+        if self.status.is_some() {
+            return;
+        }
+        self.seek_entry(to);
+        if self.skip_range_deletions_forward() && self.check_rocksdb_key() {
+            self.resolve_current_value();
+        }
+    }
+
+    fn prev(&mut self) -> bool {
+        if self.status.is_some() {
+            return false;
+        }
+        self.prev_entry();
+        self.skip_range_deletions_backward()
+            && self.check_rocksdb_key()
+            && self.resolve_current_value()
+    }
+
+    fn reset(&mut self) {
+        self.index_block.reset();
+        self.current_block = None;
+        self.value = None;
+    }
+
+    // This iterator is special in that it's valid even before the first call to advance(). It
+    // behaves correctly, though.
+    fn valid(&self) -> bool {
+        self.status.is_none()
+            && self.current_block.is_some()
+            && (self.current_block.as_ref().unwrap().valid())
+    }
+
+    // An iterator that is not valid has either reached the end of the table, or this returns an
+    // error. This also returns the error of a data block that was skipped, even if the iterator is
+    // still valid.
+    fn status(&self) -> Result<()> {
+        match self.status.as_ref().or(self.block_error.as_ref()) {
+            Some(e) => Err(e.clone()),
+            None => self.index_block.status(),
+        }
+    }
+
+    fn current(&self, key: &mut Vec<u8>, val: &mut Vec<u8>) -> bool {
+        if !self.valid() {
+            return false;
+        }
+        let cb = self.current_block.as_ref().unwrap();
+        match self.value {
+            Some(ref value) => {
+                key.clear();
+                key.extend_from_slice(cb.current_key().unwrap_or_default());
+                val.clear();
+                val.extend_from_slice(value);
+                true
+            }
+            None => cb.current(key, val),
+        }
+    }
+
+    fn current_key(&self) -> Option<&[u8]> {
+        if let Some(ref cb) = self.current_block {
+            cb.current_key()
+        } else {
+            None
+        }
+    }
+}
+
+/// PartitionedIndexIter iterates over the entries of the index partitions of a table with a
+/// partitioned index, in order, reading each partition when it is reached. If a partition can't
+/// be read, the iterator becomes invalid, and `status()` returns the error; it doesn't skip the
+/// partition, as that would silently hide all data blocks behind it.
+struct PartitionedIndexIter {
+    table: Table,
+    top_level: BlockIter,
+    partition: Option<BlockIter>,
+    ropt: ReadOptions,
+    status: Option<Status>,
+}
+
+impl PartitionedIndexIter {
+    // Reads the partition the top-level index is positioned at. Returns false if there is none,
+    // or if it can't be read.
+    fn load_partition(&mut self) -> bool {
+        self.partition = None;
+        let handle = match current_key_val(&self.top_level) {
+            Some((_, handle)) => BlockHandle::decode(&handle).0,
+            None => return false,
+        };
+        match self.table.read_block(&handle, &self.ropt) {
+            Ok(block) => {
+                self.partition = Some(block.iter());
+                true
+            }
+            Err(e) => {
+                self.status = Some(e);
+                self.reset();
+                false
+            }
+        }
+    }
+
+    // Moves to the first entry of the next non-empty partition.
+    fn next_partition(&mut self) -> bool {
+        while self.top_level.advance() {
+            if !self.load_partition() {
+                return false;
+            }
+            if self.partition.as_mut().unwrap().advance() {
+                return true;
+            }
+        }
+        self.reset();
+        false
+    }
+}
+
+// Once an error is recorded in `status`, the iterator stays invalid.
+impl SSIterator for PartitionedIndexIter {
+    fn advance(&mut self) -> bool {
+        if self.status.is_some() {
+            return false;
+        }
+        if let Some(ref mut partition) = self.partition {
+            if partition.advance() {
+                return true;
+            }
+        }
+        self.next_partition()
+    }
+
+    fn seek(&mut self, to: &[u8]) {
+        if self.status.is_some() {
+            return;
+        }
+        self.top_level.seek(to);
+        if !self.load_partition() {
+            self.reset();
+            return;
+        }
+        let partition = self.partition.as_mut().unwrap();
+        partition.seek(to);
+        // The partition's last separator key is at least `to`, but a corrupted partition might
+        // not contain it.
+        if !partition.valid() {
+            self.next_partition();
+        }
+    }
+
+    fn prev(&mut self) -> bool {
+        if self.status.is_some() {
+            return false;
+        }
+        if let Some(ref mut partition) = self.partition {
+            if partition.prev() {
+                return true;
+            }
+        }
+        while self.top_level.prev() {
+            if !self.load_partition() {
+                return false;
+            }
+            let partition = self.partition.as_mut().unwrap();
+            // seek_to_last() panics on empty blocks.
+            if partition.number_restarts() > 0 {
+                partition.seek_to_last();
+                return true;
+            }
+        }
+        self.reset();
+        false
+    }
+
+    fn reset(&mut self) {
+        self.top_level.reset();
+        self.partition = None;
+    }
+
+    fn valid(&self) -> bool {
+        self.status.is_none() && self.partition.as_ref().is_some_and(|p| p.valid())
+    }
+
+    fn status(&self) -> Result<()> {
+        match self.status {
+            Some(ref e) => Err(e.clone()),
+            None => Ok(()),
+        }
+    }
+
+    fn current(&self, key: &mut Vec<u8>, val: &mut Vec<u8>) -> bool {
+        self.valid() && self.partition.as_ref().unwrap().current(key, val)
+    }
+
+    fn current_key(&self) -> Option<&[u8]> {
+        if !self.valid() {
+            return None;
+        }
+        self.partition.as_ref().unwrap().current_key()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::filter::{BlockedBloomPolicy, XorPolicy};
+    use crate::options::{BlockFormat, CompressionType, DataBlockIndexType, FilterType, IndexType};
+    use crate::table_builder::TableBuilder;
+    use crate::test_util::{test_iterator_properties, SSIteratorIter};
+    use crate::types::{current_key_val, SSIterator};
+
+    use super::*;
+
+    use integer_encoding::FixedInt;
+
+    const LOCK_POISONED: &str = "Lock poisoned";
+
+    fn build_data() -> Vec<(&'static str, &'static str)> {
+        vec![
+            // block 1
+            ("abc", "def"),
+            ("abd", "dee"),
+            ("bcd", "asa"),
+            // block 2
+            ("bsr", "a00"),
+            ("xyz", "xxx"),
+            ("xzz", "yyy"),
+            // block 3
+            ("zzz", "111"),
+        ]
+    }
+
+    // Build a table containing raw keys (no format), with small blocks. It returns (vector,
+    // length) for convenience reason, a call f(v, v.len()) doesn't work for borrowing reasons.
+    fn build_table(data: Vec<(&'static str, &'static str)>) -> (Vec<u8>, usize) {
+        build_table_with(build_table_options(), &data)
+    }
+
+    // The options used by `build_table()`.
+    fn build_table_options() -> Options {
+        let mut opt = Options::default();
+        opt.block_restart_interval = 2;
+        opt.block_size = 32;
+        opt.compression_type = CompressionType::CompressionSnappy;
+        opt
+    }
+
+    // Build a table containing `data` (which must be sorted) with `opt`.
+    fn build_table_with<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        opt: Options,
+        data: &[(K, V)],
+    ) -> (Vec<u8>, usize) {
+        let mut d = Vec::with_capacity(512);
+        {
+            let mut b = TableBuilder::new(opt, &mut d);
+            for (k, v) in data.iter() {
+                b.add(k.as_ref(), v.as_ref()).unwrap();
+            }
+            b.finish().unwrap();
+        }
+        let size = d.len();
+        (d, size)
+    }
+
+    fn wrap_buffer(src: Vec<u8>) -> Box<dyn RandomAccess> {
+        Box::new(src)
+    }
+
+    #[test]
+    fn test_table_approximate_offset() {
+        let (src, size) = build_table(build_data());
+        let mut opt = Options::default();
+        opt.block_size = 32;
+        let table = Table::new(opt, wrap_buffer(src), size).unwrap();
+        let mut iter = table.iter();
+
+        let expected_offsets = [0, 0, 0, 44, 44, 44, 89];
+        for (i, (k, _)) in SSIteratorIter::wrap(&mut iter).enumerate() {
+            assert_eq!(expected_offsets[i], table.approx_offset_of(&k).unwrap());
+        }
+
+        // Key-past-last returns offset of metaindex block.
+        assert_eq!(137, table.approx_offset_of("{aa".as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_table_block_cache_use() {
+        let (src, size) = build_table(build_data());
+        let mut opt = Options::default();
+        opt.block_size = 32;
+
+        let table = Table::new(opt.clone(), wrap_buffer(src), size).unwrap();
+        let mut iter = table.iter();
+
+        // index/metaindex blocks are not cached. That'd be a waste of memory.
+        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 0);
+
+        iter.next();
+        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 1);
+
+        // This may fail if block parameters or data change. In that case, adapt it.
+        iter.next();
+        iter.next();
+        iter.next();
+        iter.next();
+        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 2);
+    }
+
+    #[test]
+    fn test_table_block_tiny_cache() {
+        let (src, size) = build_table(build_data());
+        // Create a table with no block cache
+        let mut opt = Options::default().with_cache_capacity(1);
+        opt.block_size = 32;
+
+        let table = Table::new(opt.clone(), wrap_buffer(src), size).unwrap();
+        let mut iter = table.iter();
+
+        // index/metaindex blocks are not cached. That'd be a waste of memory.
+        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 0);
+
+        // We should have at most one item in the cache
+        iter.next();
+        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 1);
+        iter.next();
+        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 1);
+    }
+
+    #[test]
+    fn test_table_partitioned_index() {
+        let mut opt = Options::default();
+        opt.block_size = 64;
+        opt.index_type = IndexType::Partitioned;
+
+        let keys: Vec<String> = (0..500).map(|i| format!("key{:05}", i * 2)).collect();
+        let data: Vec<_> = keys.iter().map(|k| (k, k)).collect();
+        let (src, size) = build_table_with(opt.clone(), &data);
+
+        let table = Table::new(opt, wrap_buffer(src), size).unwrap();
+        assert!(table
+            .footer
+            .has_feature(table_builder::FEATURE_PARTITIONED_INDEX));
+        // The top-level index only has a few entries.
+        let mut top_index = table.index_block().unwrap().iter();
+        let partitions = SSIteratorIter::wrap(&mut top_index).count();
+        assert!(partitions > 1 && partitions < 50);
+
+        for k in keys.iter() {
+            assert_eq!(table.get(k.as_bytes()), Ok(Some(k.as_bytes().to_vec())));
+        }
+        assert!(table.get(b"key00001").unwrap().is_none());
+        assert!(table.get(b"key99999").unwrap().is_none());
+
+        let mut iter = table.iter();
+        let all: Vec<Vec<u8>> = SSIteratorIter::wrap(&mut iter).map(|(k, _)| k).collect();
+        assert_eq!(all.len(), keys.len());
+        assert!(all.iter().zip(keys.iter()).all(|(a, b)| a == b.as_bytes()));
+
+        iter.seek(b"key00501");
+        assert_eq!(iter.current_key(), Some(b"key00502".as_ref()));
+        iter.seek(b"key00000");
+        assert_eq!(iter.current_key(), Some(b"key00000".as_ref()));
+        iter.seek(b"key99999");
+        assert!(!iter.valid());
+
+        // Walk backwards over the whole table.
+        iter.seek(b"key00998");
+        let mut n = 1;
+        while iter.prev() {
+            n += 1;
+        }
+        assert_eq!(n, keys.len());
+
+        let mut prev_offset = 0;
+        for k in keys.iter() {
+            let offset = table.approx_offset_of(k.as_bytes()).unwrap();
+            assert!(offset >= prev_offset);
+            prev_offset = offset;
+        }
+        assert!(prev_offset > 0);
+
+        // The index entries are read from the partitions.
+        let mut index_iter = table.index_iter(&ReadOptions::default()).unwrap();
+        let n = SSIteratorIter::wrap(&mut index_iter).count();
+        assert_eq!(n, table.stats().unwrap().data_blocks);
+        assert!(n > partitions);
+        index_iter.seek(b"key00501");
+        assert!(index_iter.prev());
+        assert!(index_iter.status().is_ok());
+    }
+
+    #[test]
+    fn test_table_partitioned_index_corrupted() {
+        let mut opt = Options::default();
+        opt.block_size = 64;
+        opt.index_type = IndexType::Partitioned;
+
+        let keys: Vec<String> = (0..500).map(|i| format!("key{:05}", i)).collect();
+        let data: Vec<_> = keys.iter().map(|k| (k, k)).collect();
+        let (mut src, size) = build_table_with(opt.clone(), &data);
+
+        // Corrupt the second index partition.
+        let table = Table::new(opt.clone(), wrap_buffer(src.clone()), size).unwrap();
+        let mut top_index = table.index_block().unwrap().iter();
+        top_index.advance();
+        let (first_partition_end, _) = current_key_val(&top_index).unwrap();
+        top_index.advance();
+        let (_, handle) = current_key_val(&top_index).unwrap();
+        src[BlockHandle::decode(&handle).0.offset()] ^= 0xff;
+        let table = Table::new(opt, wrap_buffer(src), size).unwrap();
+
+        assert_eq!(table.verify().unwrap_err().code, StatusCode::Corruption);
+        assert!(table.stats().is_err());
+        assert!(table.blocks().is_err());
+        assert!(table.split_points(4).is_err());
+        // Only lookups in the corrupted partition fail.
+        let corrupted = keys
+            .iter()
+            .find(|k| k.as_bytes() > first_partition_end.as_slice())
+            .unwrap()
+            .as_bytes();
+        assert!(table.approx_offset_of(corrupted).is_err());
+        assert!(table.get(corrupted).is_err());
+        for k in [&keys[0], &keys[499]] {
+            assert_eq!(table.get(k.as_bytes()), Ok(Some(k.as_bytes().to_vec())));
+        }
+
+        // Iterators stop before the partition.
+        let mut iter = table.iter();
+        let n = SSIteratorIter::wrap(&mut iter).count();
+        assert!(n > 0 && n < keys.len());
+        assert_eq!(iter.status().unwrap_err().code, StatusCode::Corruption);
+    }
+
+    #[test]
+    fn test_table_full_filter() {
+        let mut opt = Options::default();
+        opt.block_size = 32;
+        opt.filter_type = FilterType::Full;
+        opt.cache_index_and_filter_blocks = true;
+
+        let (src, size) = build_table_with(opt.clone(), &build_data());
+
+        let table = Table::new(opt.clone(), wrap_buffer(src), size).unwrap();
+        let filters = table.filter_block().unwrap().unwrap();
+        assert!(filters.is_full());
+        assert_eq!(filters.num(), 1);
+        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 1);
+
+        // Absent keys are rejected without reading the index block.
+        assert!(table.get(b"abb").unwrap().is_none());
+        assert!(table.get(b"xyy").unwrap().is_none());
+        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 1);
+
+        for &(k, v) in build_data().iter() {
+            assert_eq!(table.get(k.as_bytes()), Ok(Some(v.as_bytes().to_vec())));
+        }
+    }
+
+    #[test]
+    fn test_table_blocked_bloom_filter() {
+        let mut opt = Options::default();
+        opt.filter_policy = Arc::new(Box::new(BlockedBloomPolicy::new(10)));
+
+        let (src, size) = build_table_with(opt.clone(), &build_data());
+
+        let table = Table::new(opt, wrap_buffer(src.clone()), size).unwrap();
+        assert!(table.filter_block().unwrap().is_some());
+        assert_eq!(table.filter_name(), Some("sstable.BlockedBloomFilter"));
+        assert!(table.get(b"abb").unwrap().is_none());
+
+        // A reader configured with a different policy doesn't use the filter if the table's policy
+        // isn't registered.
+        let mut opt = Options::default();
+        opt.filter_policies.clear();
+        let table = Table::new(opt, wrap_buffer(src), size).unwrap();
+        assert!(table.filter_block().unwrap().is_none());
+        assert_eq!(table.filter_name(), None);
+        for &(k, v) in build_data().iter() {
+            assert_eq!(table.get(k.as_bytes()), Ok(Some(v.as_bytes().to_vec())));
+        }
+    }
+
+    #[test]
+    fn test_table_filter_policy_registry() {
+        let mut opt = Options::default();
+        opt.filter_policy = Arc::new(Box::new(XorPolicy::new()));
+
+        let (src, size) = build_table_with(opt, &build_data());
+
+        // The default options know all built-in policies.
+        let table = Table::new(Options::default(), wrap_buffer(src), size).unwrap();
+        assert_eq!(table.filter_name(), Some("sstable.XorFilter8"));
+        assert!(table.get(b"abb").unwrap().is_none());
+        assert_eq!(table.get(b"abd"), Ok(Some(b"dee".to_vec())));
+
+        let (src, size) = build_table(build_data());
+        let table = Table::new(Options::default(), wrap_buffer(src), size).unwrap();
+        assert_eq!(table.filter_name(), Some("leveldb.BuiltinBloomFilter2"));
+
+        let mut src = vec![];
+        {
+            let mut b = TableBuilder::new_no_filter(Options::default(), &mut src);
+            b.add(b"abc", b"def").unwrap();
+            b.finish().unwrap();
+        }
+        let size = src.len();
+        let table = Table::new(Options::default(), wrap_buffer(src), size).unwrap();
+        assert_eq!(table.filter_name(), None);
+    }
+
+    #[test]
+    fn test_table_unsupported_features() {
+        // Replaces the footer by one with the given format version and feature flags.
+        let with_footer = |format_version, features| {
+            let (mut src, _) = build_table(build_data());
+            let footer_start = src.len() - table_builder::FULL_FOOTER_LENGTH;
+            let mut footer = Footer::decode(&src[footer_start..]).unwrap();
+            footer.format_version = format_version;
+            footer.features = features;
+            src.truncate(footer_start);
+            let mut buf = [0; table_builder::MAX_FOOTER_LENGTH];
+            footer.encode(&mut buf);
+            src.extend_from_slice(&buf[..footer.encoded_length()]);
+            let size = src.len();
+            Table::new(Options::default(), wrap_buffer(src), size)
+        };
+
+        for (format_version, features) in [
+            (table_builder::FORMAT_VERSION, 1 << 31),
+            (table_builder::FORMAT_VERSION + 1, 0),
+        ] {
+            match with_footer(format_version, features) {
+                Err(e) => assert_eq!(e.code, StatusCode::NotSupported),
+                Ok(_) => panic!("table with unknown format was opened"),
+            }
+        }
+        assert!(Table::new(Options::default(), wrap_buffer(vec![0; 10]), 10).is_err());
+    }
+
+    #[test]
+    fn test_table_cache_index_and_filter_blocks() {
+        let mut opt = build_table_options();
+        opt.properties_block = true;
+        let (src, size) = build_table_with(opt, &build_data());
+        let mut opt = Options::default();
+        opt.cache_index_and_filter_blocks = true;
+
+        let table = Table::new(opt.clone(), wrap_buffer(src), size).unwrap();
+        assert!(table.index_block.read().unwrap().is_none());
+        assert!(table.filters.read().unwrap().is_none());
+        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 0);
+
+        // index, filter and data block.
+        assert_eq!(table.get(b"abc"), Ok(Some(b"def".to_vec())));
+        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 3);
+        // The filter block is used from the cache.
+        assert!(table.get(b"abca").unwrap().is_none());
+        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 3);
+        assert!(table.index_block.read().unwrap().is_none());
+        assert!(table.filters.read().unwrap().is_none());
+
+        let mut iter = table.iter();
+        assert_eq!(SSIteratorIter::wrap(&mut iter).count(), build_data().len());
+        assert_eq!(0, table.approx_offset_of(b"abc").unwrap());
+
+        // The properties block is read when needed, too.
+        assert!(table.properties.read().unwrap().is_none());
+        let count = opt.block_cache.read().expect(LOCK_POISONED).count();
+        assert_eq!(
+            table.properties().unwrap().unwrap().num_entries,
+            build_data().len()
+        );
+        assert_eq!(
+            opt.block_cache.read().expect(LOCK_POISONED).count(),
+            count + 1
+        );
+        assert!(table.properties.read().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_table_pin_index_and_filter_blocks() {
+        let (src, size) = build_table(build_data());
+        let mut opt = Options::default().with_cache_capacity(1);
+        opt.cache_index_and_filter_blocks = true;
+        opt.pin_index_and_filter_blocks = true;
+
+        let table = Table::new(opt.clone(), wrap_buffer(src), size).unwrap();
+        assert!(table.index_block.read().unwrap().is_none());
+
+        assert_eq!(table.get(b"bsr"), Ok(Some(b"a00".to_vec())));
+        assert!(table.index_block.read().unwrap().is_some());
+        assert!(table.filters.read().unwrap().is_some());
+        assert!(table.get(b"bsa").unwrap().is_none());
+
+        // Clones share the pinned blocks.
+        let table2 = table.clone();
+        assert!(table2.index_block.read().unwrap().is_some());
+        let mut iter = table2.iter();
+        assert_eq!(SSIteratorIter::wrap(&mut iter).count(), build_data().len());
+    }
+
+    #[test]
+    fn test_table_unreadable_index() {
+        let (mut src, size) = build_table(build_data());
+        let mut opt = Options::default();
+        opt.cache_index_and_filter_blocks = true;
+
+        let table = Table::new(opt.clone(), wrap_buffer(src.clone()), size).unwrap();
+        let mut iter = table.iter();
+        assert!(iter.advance());
+        assert_eq!(iter.status(), Ok(()));
+
+        // The index block is only read when needed, so the table can still be opened.
+        let (index_offset, _) = table.stats().unwrap().index;
+        src[index_offset] ^= 0x01;
+        let table = Table::new(opt, wrap_buffer(src), size).unwrap();
+
+        let mut iter = table.iter();
+        assert!(!iter.advance());
+        assert_eq!(iter.status().unwrap_err().code, StatusCode::Corruption);
+        assert_eq!(
+            table.approx_offset_of(b"abc").unwrap_err().code,
+            StatusCode::Corruption
+        );
+        assert_eq!(
+            table.approx_size(b"abc", b"zzz").unwrap_err().code,
+            StatusCode::Corruption
+        );
+        assert_eq!(
+            table.approx_entries(b"abc", b"zzz").unwrap_err().code,
+            StatusCode::Corruption
+        );
+    }
+
+    #[test]
+    fn test_table_read_options_fill_cache() {
+        let (src, size) = build_table(build_data());
+        let mut opt = Options::default();
+        opt.block_size = 32;
+
+        let table = Table::new(opt.clone(), wrap_buffer(src), size).unwrap();
+        let ropt = ReadOptions {
+            fill_cache: false,
+            ..ReadOptions::default()
+        };
+
+        let mut iter = table.iter_with_options(&ropt);
+        assert_eq!(SSIteratorIter::wrap(&mut iter).count(), build_data().len());
+        assert_eq!(
+            table.get_with_options(b"bsr", &ropt),
+            Ok(Some(b"a00".to_vec()))
+        );
+        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 0);
+
+        // Blocks that are already cached are still used.
+        assert!(table.get(b"bsr").unwrap().is_some());
+        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 1);
+        assert!(table.get_with_options(b"bsr", &ropt).unwrap().is_some());
+        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 1);
+    }
+
+    #[test]
+    fn test_table_read_options_verify_checksums() {
+        let (mut src, size) = build_table_with(Options::default(), &build_data());
+        // Modify the value of the first entry ("def" -> "dff").
+        src[7] += 1;
+
+        let table = Table::new(Options::default(), wrap_buffer(src), size).unwrap();
+        assert!(table.get(b"abc").is_err());
+
+        let ropt = ReadOptions {
+            verify_checksums: false,
+            ..ReadOptions::default()
+        };
+        assert_eq!(
+            table.get_with_options(b"abc", &ropt),
+            Ok(Some(b"dff".to_vec()))
+        );
+        let mut iter = table.iter_with_options(&ropt);
+        assert_eq!(SSIteratorIter::wrap(&mut iter).count(), build_data().len());
+    }
+
+    #[test]
+    fn test_table_stats_and_verify() {
+        let mut opt = build_table_options();
+        opt.properties_block = true;
+        let (mut src, size) = build_table_with(opt, &build_data());
+        let table = Table::new(Options::default(), wrap_buffer(src.clone()), size).unwrap();
+
+        let stats = table.stats().unwrap();
+        assert_eq!(stats.data_blocks, 3);
+        assert!(stats.compressed_data_blocks <= 3);
+        assert_eq!(stats.format_version, table_builder::FORMAT_VERSION_LEVELDB);
+        assert!(stats.features.is_empty());
+        assert_eq!(stats.filter.unwrap().0, table.filter_name().unwrap());
+        assert_eq!(
+            stats.data_size,
+            table.properties().unwrap().unwrap().data_size
+        );
+        assert_eq!(stats.range_filter_size, None);
+        assert_eq!(table.verify(), Ok(build_data().len()));
+
+        // Corrupt the second data block; iterators skip it, verify() doesn't.
+        let mut iter = table.index_iter(&ReadOptions::default()).unwrap();
+        SSIterator::next(&mut iter);
+        let (_, handle) = SSIterator::next(&mut iter).unwrap();
+        src[BlockHandle::decode(&handle).0.offset() + 1] ^= 0xff;
+        let table = Table::new(Options::default(), wrap_buffer(src), size).unwrap();
+        let mut iter = table.iter();
+        assert_eq!(SSIteratorIter::wrap(&mut iter).count(), 4);
+        assert_eq!(table.verify().unwrap_err().code, StatusCode::Corruption);
+    }
+
+    #[test]
+    fn test_table_checksum_types() {
+        for ctype in [
+            ChecksumType::Crc32c,
+            ChecksumType::XxHash,
+            ChecksumType::XxHash64,
+            ChecksumType::Xxh3,
+        ] {
+            let mut opt = Options::default();
+            opt.block_size = 32;
+            opt.compression_type = CompressionType::CompressionSnappy;
+            opt.checksum_type = ctype;
+            let (mut src, size) = build_table_with(opt.clone(), &build_data());
+
+            // The table's checksum type is used, not the one of the reader's options.
+            let table = Table::new(Options::default(), wrap_buffer(src.clone()), size).unwrap();
+            let stats = table.stats().unwrap();
+            assert_eq!(stats.checksum_type, ctype);
+            // The checksum type is recorded in the footer, not as a feature.
+            assert!(stats.features.is_empty());
+            // LevelDB can only read tables with CRC32C checksums.
+            let format_version = match ctype {
+                ChecksumType::Crc32c => table_builder::FORMAT_VERSION_LEVELDB,
+                _ => table_builder::FORMAT_VERSION,
+            };
+            assert_eq!(stats.format_version, format_version);
+            assert_eq!(table.verify(), Ok(build_data().len()));
+            assert_eq!(table.get(b"xyz"), Ok(Some(b"xxx".to_vec())));
+
+            src[1] ^= 0x01;
+            let table = Table::new(Options::default(), wrap_buffer(src), size).unwrap();
+            assert_eq!(table.verify().unwrap_err().code, StatusCode::Corruption);
+        }
+    }
+
+    #[test]
+    fn test_table_file_checksum() {
+        let (src, size) = build_table(build_data());
+        let table = Table::new(Options::default(), wrap_buffer(src), size).unwrap();
+        assert_eq!(
+            table.verify_file_checksum().unwrap_err().code,
+            StatusCode::NotSupported
+        );
+
+        let mut opt = Options::default();
+        opt.block_size = 32;
+        opt.file_checksum = true;
+        let (src, size) = build_table_with(opt.clone(), &build_data());
+        let table = Table::new(opt.clone(), wrap_buffer(src.clone()), size).unwrap();
+        assert_eq!(table.verify_file_checksum(), Ok(()));
+        assert_eq!(table.stats().unwrap().features, vec!["file_checksum"]);
+        assert_eq!(table.get(b"abc"), Ok(Some(b"def".to_vec())));
+
+        // Swapping two bytes keeps the table readable, but changes the checksum.
+        let mut swapped = src.clone();
+        swapped.swap(3, 4);
+        assert_ne!(swapped, src);
+        let table = Table::new(opt.clone(), wrap_buffer(swapped), size).unwrap();
+        assert_eq!(
+            table.verify_file_checksum().unwrap_err().code,
+            StatusCode::Corruption
+        );
+
+        // Bytes inserted before the footer change the file length.
+        let mut longer = src.clone();
+        longer.insert(size - table.footer.encoded_length(), 0);
+        let table = Table::new(opt, wrap_buffer(longer), size + 1).unwrap();
+        assert_eq!(
+            table.verify_file_checksum().unwrap_err().code,
+            StatusCode::Corruption
+        );
+    }
+
+    #[test]
+    fn test_table_blocks() {
+        let (src, size) = build_table(build_data());
+        let table = Table::new(Options::default(), wrap_buffer(src), size).unwrap();
+
+        let blocks = table.blocks().unwrap();
+        let summary = blocks
+            .iter()
+            .map(|b| {
+                (
+                    b.entries,
+                    b.restarts,
+                    String::from_utf8(b.first_key.clone()).unwrap(),
+                    String::from_utf8(b.last_key.clone()).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (3, 2, "abc".to_string(), "bcd".to_string()),
+                (3, 2, "bsr".to_string(), "xzz".to_string()),
+                (1, 1, "zzz".to_string(), "zzz".to_string()),
+            ]
+        );
+        let mut offset = 0;
+        for b in blocks.iter() {
+            assert_eq!(b.offset, offset);
+            assert_eq!(b.compression, CompressionType::CompressionSnappy);
+            assert!(b.uncompressed_size > 0);
+            offset += b.size + 5;
+        }
+
+        let meta_blocks = table.meta_blocks().unwrap();
+        assert_eq!(meta_blocks.len(), 1);
+        assert_eq!(
+            meta_blocks[0].name,
+            format!("filter.{}", table.filter_name().unwrap()).into_bytes()
+        );
+        assert_eq!(meta_blocks[0].offset, offset);
+        assert_eq!(
+            Some(meta_blocks[0].size),
+            table.stats().unwrap().filter.map(|f| f.1)
+        );
+    }
+
+    #[test]
+    fn test_table_blocks_empty_block() {
+        // TableBuilder doesn't write empty data blocks, so assemble a table with one by hand.
+        let opt = Options::default();
+        let mut src = vec![];
+        let mut write_block = |contents: Vec<u8>| {
+            let handle = BlockHandle::new(src.len(), contents.len());
+            let cksum = table_block::block_checksum(opt.checksum_type, &contents, 0);
+            src.extend_from_slice(&contents);
+            src.push(0);
+            src.extend_from_slice(&cksum.encode_fixed_vec());
+            handle
+        };
+        let data = write_block(BlockBuilder::new(opt.clone()).finish());
+        let mut index = BlockBuilder::new(opt.clone());
+        let mut handle_enc = [0; 16];
+        let enc_len = data.encode_to(&mut handle_enc);
+        index.add(b"a", &handle_enc[..enc_len]);
+        let index = write_block(index.finish());
+        let metaindex = write_block(BlockBuilder::new(opt.clone()).finish());
+        let footer = Footer::new(metaindex, index);
+        let mut footer_enc = vec![0; footer.encoded_length()];
+        footer.encode(&mut footer_enc);
+        src.extend_from_slice(&footer_enc);
+
+        let size = src.len();
+        let table = Table::new(opt, wrap_buffer(src), size).unwrap();
+        let blocks = table.blocks().unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].entries, 0);
+        assert!(blocks[0].first_key.is_empty());
+        assert!(blocks[0].last_key.is_empty());
+    }
+
+    #[test]
+    fn test_table_approx_size() {
+        let mut opt = Options::default();
+        opt.block_size = 256;
+        opt.properties_block = true;
+        let data: Vec<_> = (0..1000)
+            .map(|i| (format!("key{:04}", i), "value"))
+            .collect();
+        let (src, size) = build_table_with(opt.clone(), &data);
+        let table = Table::new(opt, wrap_buffer(src), size).unwrap();
+
+        let props = table.properties().unwrap().unwrap();
+        assert_eq!(props.num_entries, 1000);
+        assert_eq!(props.raw_key_size, 7000);
+        assert_eq!(props.raw_value_size, 5000);
+        assert_eq!(props.num_data_blocks, table.blocks().unwrap().len());
+
+        assert_eq!(table.approx_size(b"", b"zzz").unwrap(), props.data_size);
+        assert_eq!(table.approx_size(b"key0500", b"key0500").unwrap(), 0);
+        assert_eq!(table.approx_size(b"key0600", b"key0500").unwrap(), 0);
+        assert_eq!(table.approx_entries(b"", b"zzz").unwrap(), 1000);
+        let single = table.approx_size(b"key0500", b"key0501").unwrap();
+        // At most two blocks.
+        assert!(single > 0 && single <= 2 * (256 + 32));
+
+        for t in [table.clone(), {
+            // Without properties, the first block of the range is sampled.
+            *table.properties.write().unwrap() = None;
+            table
+        }] {
+            let half = t.approx_entries(b"key0250", b"key0750").unwrap();
+            assert!((450..550).contains(&half), "{}", half);
+            let half = t.approx_size(b"key0250", b"key0750").unwrap();
+            assert!(2 * half > props.data_size * 9 / 10 && 2 * half < props.data_size * 11 / 10);
+        }
+    }
+
+    #[test]
+    fn test_table_split() {
+        let mut opt = Options::default();
+        opt.block_size = 128;
+        let data: Vec<(Vec<u8>, Vec<u8>)> = (0..1000)
+            .map(|i| (format!("key{:04}", i).into_bytes(), b"value".to_vec()))
+            .collect();
+        let (src, size) = build_table_with(opt.clone(), &data);
+        let table = Table::new(opt, wrap_buffer(src), size).unwrap();
+
+        let points = table.split_points(4).unwrap();
+        assert_eq!(points.len(), 3);
+        let mut bounds = vec![b"".to_vec()];
+        bounds.extend(points.iter().cloned());
+        bounds.push(b"zzz".to_vec());
+        let data_size = table.approx_size(b"", b"zzz").unwrap();
+        for w in bounds.windows(2) {
+            let size = table.approx_size(&w[0], &w[1]).unwrap();
+            assert!(size * 4 > data_size * 8 / 10 && size * 4 < data_size * 12 / 10);
+        }
+        assert_eq!(table.split_points(1).unwrap().len(), 0);
+        let num_blocks = table.blocks().unwrap().len();
+        assert_eq!(table.split_points(10000).unwrap().len(), num_blocks - 1);
+
+        // Iterate over the ranges in parallel.
+        fn assert_send<T: Send>(_: &T) {}
+        let ranges = table.split(4).unwrap();
+        assert_eq!(ranges.len(), 4);
+        assert_send(&ranges[0]);
+        let parts: Vec<Vec<(Vec<u8>, Vec<u8>)>> = std::thread::scope(|s| {
+            let handles: Vec<_> = ranges
+                .into_iter()
+                .map(|range| {
+                    s.spawn(move || {
+                        let mut iter = range.iter();
+                        SSIteratorIter::wrap(&mut iter).collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert!(parts.iter().all(|p| !p.is_empty()));
+        assert_eq!(parts.concat(), data);
+    }
+
+    #[test]
+    fn test_table_data_block_hash_index() {
+        let mut opt = Options::default();
+        opt.block_size = 64;
+        opt.block_restart_interval = 2;
+        opt.data_block_index_type = DataBlockIndexType::BinaryAndHash;
+
+        let data: Vec<(Vec<u8>, Vec<u8>)> = (0..200)
+            .map(|i| {
+                (
+                    format!("key{:04}", i * 2).into_bytes(),
+                    format!("val{}", i).into_bytes(),
+                )
+            })
+            .collect();
+        let mut src = vec![];
+        {
+            let mut b = TableBuilder::new_no_filter(opt.clone(), &mut src);
+            for (k, v) in data.iter() {
+                b.add(k, v).unwrap();
+            }
+            b.finish().unwrap();
+        }
+        let size = src.len();
+        let table = Table::new(opt.clone(), wrap_buffer(src), size).unwrap();
+        assert!(table
+            .footer
+            .has_feature(table_builder::FEATURE_DATA_BLOCK_HASH_INDEX));
+
+        for (k, v) in data.iter() {
+            assert_eq!(table.get(k), Ok(Some(v.clone())));
+        }
+        for i in 0..200 {
+            assert_eq!(
+                table.get(format!("key{:04}", i * 2 + 1).as_bytes()),
+                Ok(None)
+            );
+        }
+
+        let mut iter = table.iter();
+        assert_eq!(SSIteratorIter::wrap(&mut iter).count(), data.len());
+        iter.seek(b"key0101");
+        assert_eq!(iter.current_key(), Some(&b"key0102"[..]));
+    }
+
+    #[test]
+    fn test_table_internal_keys() {
+        // key "a" has versions 1..=20 (every 5th is a deletion), spanning several blocks; "b"
+        // and "d" have a single version each.
+        let mut data = vec![];
+        for seq in (1..=20).rev() {
+            let t = if seq % 5 == 0 {
+                ValueType::TypeDeletion
+            } else {
+                ValueType::TypeValue
+            };
+            data.push((
+                key_types::build_internal_key(b"a", seq, t),
+                format!("a{}", seq).into_bytes(),
+            ));
+        }
+        data.push((
+            key_types::build_internal_key(b"b", 30, ValueType::TypeValue),
+            b"b30".to_vec(),
+        ));
+        data.push((
+            key_types::build_internal_key(b"d", 10, ValueType::TypeDeletion),
+            vec![],
+        ));
+
+        for (filter_type, index_type) in [
+            (FilterType::PerBlock, DataBlockIndexType::BinarySearch),
+            (FilterType::Full, DataBlockIndexType::BinaryAndHash),
+        ] {
+            let mut opt = Options::default().with_internal_keys();
+            opt.block_size = 64;
+            opt.block_restart_interval = 2;
+            opt.filter_type = filter_type;
+            opt.data_block_index_type = index_type;
+
+            let (src, size) = build_table_with(opt.clone(), &data);
+            let table = Table::new(opt.clone(), wrap_buffer(src), size).unwrap();
+            assert!(table.blocks().unwrap().len() > 3);
+            assert!(table.filter_name().is_some());
+
+            for seq in 1..=25 {
+                let expected = match seq.min(20) {
+                    s if s % 5 == 0 => LookupResult::Deleted { seq: s },
+                    s => LookupResult::Value {
+                        seq: s,
+                        value: format!("a{}", s).into_bytes(),
+                    },
+                };
+                assert_eq!(table.get_at(b"a", seq), Ok(Some(expected)));
+            }
+            assert_eq!(table.get_at(b"a", 0), Ok(None));
+            assert_eq!(table.get_at(b"b", 29), Ok(None));
+            assert_eq!(
+                table.get_at(b"b", key_types::MAX_SEQUENCE_NUMBER),
+                Ok(Some(LookupResult::Value {
+                    seq: 30,
+                    value: b"b30".to_vec()
+                }))
+            );
+            assert_eq!(table.get_at(b"c", 100), Ok(None));
+            assert_eq!(
+                table.get_at(b"d", 100),
+                Ok(Some(LookupResult::Deleted { seq: 10 }))
+            );
+            assert_eq!(table.get_at(b"e", 100), Ok(None));
+            assert!(table.filter_stats().negatives > 0);
+
+            // Exact internal keys can be looked up with get(), and iteration is in internal key
+            // order.
+            for (k, v) in data.iter() {
+                assert_eq!(table.get(k), Ok(Some(v.clone())));
+            }
+            let mut iter = table.iter();
+            let keys: Vec<_> = SSIteratorIter::wrap(&mut iter).map(|(k, _)| k).collect();
+            assert_eq!(
+                keys,
+                data.iter().map(|(k, _)| k.clone()).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_table_internal_keys_range_deletions() {
+        // Keys 1 to 9 have versions 30 and 10. User keys are longer than the tag of internal keys.
+        let user_key = |i: usize| format!("user-key-{:02}", i).into_bytes();
+        let mut data = vec![];
+        for i in 1..=9 {
+            for seq in [30, 10] {
+                data.push((
+                    key_types::build_internal_key(&user_key(i), seq, ValueType::TypeValue),
+                    format!("{}@{}", i, seq).into_bytes(),
+                ));
+            }
+        }
+        let mut opt = Options::default().with_internal_keys();
+        opt.block_size = 64;
+        let mut src = vec![];
+        {
+            let mut b = TableBuilder::new(opt.clone(), &mut src);
+            for (k, v) in data.iter() {
+                b.add(k, v).unwrap();
+            }
+            b.delete_range_at(&user_key(2), &user_key(5), 20);
+            b.delete_range(&user_key(7), &user_key(8));
+            b.delete_range_at(&user_key(9), b"user-key-99", 40);
+            // Empty.
+            b.delete_range_at(&user_key(6), &user_key(6), 50);
+            b.finish().unwrap();
+        }
+        let size = src.len();
+        let table = Table::new(opt, wrap_buffer(src), size).unwrap();
+        assert_eq!(table.range_deletions().len(), 3);
+
+        let value = |i: usize, seq| {
+            Some(LookupResult::Value {
+                seq,
+                value: format!("{}@{}", i, seq).into_bytes(),
+            })
+        };
+        let deleted = |seq| Some(LookupResult::Deleted { seq });
+        for (key, seq, expected) in [
+            (user_key(1), 25, value(1, 10)),
+            // The deletion at 20 isn't visible at 15, and doesn't delete version 30.
+            (user_key(3), 15, value(3, 10)),
+            (user_key(3), 25, deleted(20)),
+            (user_key(3), 35, value(3, 30)),
+            // Ranges don't include their end.
+            (user_key(5), 25, value(5, 10)),
+            (user_key(7), 35, deleted(key_types::MAX_SEQUENCE_NUMBER)),
+            (user_key(9), 35, value(9, 30)),
+            (user_key(9), 45, deleted(40)),
+            // Keys without versions in this table, too.
+            (b"user-key-095".to_vec(), 45, deleted(40)),
+            (b"user-key-095".to_vec(), 35, None),
+        ] {
+            assert_eq!(table.get_at(&key, seq), Ok(expected), "{:?}@{}", key, seq);
+        }
+
+        let is_deleted = |i: usize, seq| match i {
+            2..=4 => seq < 20,
+            7 | 9 => true,
+            _ => false,
+        };
+        for (k, _) in data.iter() {
+            let (key, seq, _) = key_types::parse_internal_key(k).unwrap();
+            let i = key[9..].iter().fold(0, |n, d| n * 10 + (d - b'0') as usize);
+            assert_eq!(table.is_range_deleted(k), is_deleted(i, seq));
+        }
 
-        self.current_block = Some(block.iter());
-        self.current_block_off = new_block_handle.offset();
+        // Lookups apply the ranges without `hide_range_deletions`, too.
+        assert_eq!(
+            table.get_at_with_options(&user_key(7), 35, &ReadOptions::default()),
+            Ok(deleted(key_types::MAX_SEQUENCE_NUMBER))
+        );
 
-        Ok(())
+        // Iterators only hide the deleted versions.
+        let ropt = ReadOptions {
+            hide_range_deletions: true,
+            ..ReadOptions::default()
+        };
+        let mut iter = table.iter_with_options(&ropt);
+        let entries: Vec<_> = SSIteratorIter::wrap(&mut iter).collect();
+        let expected: Vec<_> = data
+            .iter()
+            .filter(|(k, _)| !table.is_range_deleted(k))
+            .cloned()
+            .collect();
+        assert_eq!(entries.len(), 11);
+        assert_eq!(entries, expected);
+
+        let mut iter = table.iter_with_options(&ropt);
+        let seek_key = |i| key_types::build_internal_key(&user_key(i), 20, ValueType::TypeValue);
+        iter.seek(&seek_key(3));
+        assert_eq!(current_key_val(&iter), Some(expected[4].clone()));
+        iter.seek(&seek_key(7));
+        assert_eq!(current_key_val(&iter), Some(expected[9].clone()));
+        // Backwards from key 8, skipping key 7 and version 10 of key 4.
+        assert!(iter.prev());
+        assert_eq!(current_key_val(&iter), Some(expected[8].clone()));
+        assert!(iter.prev());
+        assert_eq!(current_key_val(&iter), Some(expected[7].clone()));
+        assert!(iter.prev());
+        assert_eq!(current_key_val(&iter), Some(expected[6].clone()));
+        assert!(iter.prev());
+        assert_eq!(current_key_val(&iter), Some(expected[5].clone()));
+        assert!(iter.prev());
+        assert_eq!(current_key_val(&iter), Some(expected[4].clone()));
     }
-}
 
-impl SSIterator for TableIterator {
-    fn advance(&mut self) -> bool {
-        // Uninitialized case.
-        if self.current_block.is_none() {
-            match self.skip_to_next_entry() {
-                Ok(true) => return self.advance(),
-                Ok(false) => {
-                    self.reset();
-                    return false;
+    /// Builds a table in RocksDB's format from blocks of internal keys, with the given properties.
+    fn build_rocksdb_table(
+        blocks: &[Vec<(Vec<u8>, Vec<u8>)>],
+        ctype: ChecksumType,
+        props: &[(&[u8], Vec<u8>)],
+        user_key_index: bool,
+        delta_index: bool,
+    ) -> Vec<u8> {
+        let opt = Options::default().with_internal_keys();
+        let mut src = vec![];
+        let mut write_block = |contents: &[u8], compression: CompressionType| {
+            let contents = match compression {
+                CompressionType::CompressionNone => contents.to_vec(),
+                CompressionType::CompressionSnappy => {
+                    snap::raw::Encoder::new().compress_vec(contents).unwrap()
                 }
-                // try next block from index, this might be corruption
-                Err(_) => return self.advance(),
-            }
-        }
+            };
+            let handle = BlockHandle::new(src.len(), contents.len());
+            let checksum = table_block::block_checksum(ctype, &contents, compression as u8);
+            src.extend_from_slice(&contents);
+            src.push(compression as u8);
+            src.extend_from_slice(&checksum.encode_fixed_vec());
+            handle
+        };
 
-        // Initialized case -- does the current block have more entries?
-        if let Some(ref mut cb) = self.current_block {
-            if cb.advance() {
-                return true;
+        let mut index = vec![];
+        for (i, block) in blocks.iter().enumerate() {
+            let mut builder = BlockBuilder::new(opt.clone());
+            for (k, v) in block {
+                builder.add(k, v);
             }
+            let compression = if i % 2 == 0 {
+                CompressionType::CompressionSnappy
+            } else {
+                CompressionType::CompressionNone
+            };
+            let handle = write_block(&builder.finish(), compression);
+            let last = block.last().unwrap().0.clone();
+            let key = if user_key_index {
+                key_types::split_internal_key(&last).0.to_vec()
+            } else {
+                last
+            };
+            index.push((key, handle));
         }
 
-        // If the current block is exhausted, try loading the next block.
-        self.current_block = None;
-        match self.skip_to_next_entry() {
-            Ok(true) => self.advance(),
-            Ok(false) => {
-                self.reset();
-                false
-            }
-            // try next block, this might be corruption
-            Err(_) => self.advance(),
+        // RocksDB's filters are ignored.
+        let filter = write_block(b"not a filter", CompressionType::CompressionNone);
+        let mut builder = BlockBuilder::new(Options::default());
+        for (name, val) in props {
+            builder.add(name, val);
         }
+        let properties = write_block(&builder.finish(), CompressionType::CompressionNone);
+
+        let mut builder = BlockBuilder::new(Options::default());
+        let mut handle = [0; 20];
+        let len = filter.encode_to(&mut handle);
+        builder.add(b"fullfilter.rocksdb.BuiltinBloomFilter", &handle[..len]);
+        let len = properties.encode_to(&mut handle);
+        builder.add(b"rocksdb.properties", &handle[..len]);
+        let metaindex = write_block(&builder.finish(), CompressionType::CompressionNone);
+
+        let index_contents = crate::test_util::encode_rocksdb_index_block(&index, 2, delta_index);
+        let index = write_block(&index_contents, CompressionType::CompressionNone);
+
+        let mut footer = Footer::new(metaindex, index);
+        footer.rocksdb_format_version = Some(5);
+        footer.checksum_type = ctype;
+        let mut buf = [0; table_builder::ROCKSDB_FOOTER_LENGTH];
+        footer.encode(&mut buf);
+        src.extend_from_slice(&buf);
+        src
     }
 
-    // A call to valid() after seeking is necessary to ensure that the seek worked (e.g., no error
-    // while reading from disk)
-    fn seek(&mut self, to: &[u8]) {
-        // first seek in index block, rewind by one entry (so we get the next smaller index entry),
-        // then set current_block and seek there
-        self.index_block.seek(to);
+    #[test]
+    fn test_table_rocksdb() {
+        let entry = |k: &str, seq, t, v: &str| {
+            (
+                key_types::build_internal_key(k.as_bytes(), seq, t),
+                v.as_bytes().to_vec(),
+            )
+        };
+        // The versions of a user key are in the same block, as required for an index with user
+        // keys.
+        let blocks = vec![
+            vec![
+                entry("abc", 3, ValueType::TypeValue, "def"),
+                entry("abd", 4, ValueType::TypeDeletion, ""),
+                entry("abd", 2, ValueType::TypeValue, "dee"),
+            ],
+            vec![
+                entry("bcd", 5, ValueType::TypeValue, "asa"),
+                entry("bsr", 1, ValueType::TypeValue, "a00"),
+            ],
+            vec![
+                entry("xyz", 6, ValueType::TypeValue, "xxx"),
+                entry("zzz", 7, ValueType::TypeValue, "111"),
+            ],
+        ];
+        let props = |comparator: &str, user_key_index: bool, delta_index: bool| {
+            vec![
+                (
+                    &b"rocksdb.block.based.table.index.type"[..],
+                    0u32.encode_fixed_vec(),
+                ),
+                (b"rocksdb.comparator", comparator.as_bytes().to_vec()),
+                (b"rocksdb.compression", b"Snappy".to_vec()),
+                (
+                    b"rocksdb.index.key.is.user.key",
+                    (user_key_index as usize).encode_var_vec(),
+                ),
+                (
+                    b"rocksdb.index.value.is.delta.encoded",
+                    (delta_index as usize).encode_var_vec(),
+                ),
+                (b"rocksdb.num.data.blocks", 3usize.encode_var_vec()),
+                (b"rocksdb.num.entries", 7usize.encode_var_vec()),
+            ]
+        };
 
-        // It's possible that this is a seek past-last; reset in that case.
-        if let Some((past_block, handle)) = current_key_val(&self.index_block) {
-            if self.table.opt.cmp.cmp(to, &past_block) <= Ordering::Equal {
-                // ok, found right block: continue
-                if let Ok(()) = self.load_block(&handle) {
-                    // current_block is always set if load_block() returned Ok.
-                    self.current_block.as_mut().unwrap().seek(to);
-                    return;
+        for (ctype, user_key_index, delta_index) in [
+            (ChecksumType::Crc32c, false, false),
+            (ChecksumType::XxHash, true, false),
+            (ChecksumType::XxHash64, false, true),
+            (ChecksumType::Xxh3, true, true),
+            (ChecksumType::NoChecksum, true, true),
+        ] {
+            let src = build_rocksdb_table(
+                &blocks,
+                ctype,
+                &props("leveldb.BytewiseComparator", user_key_index, delta_index),
+                user_key_index,
+                delta_index,
+            );
+            let size = src.len();
+
+            for cache_index in [false, true] {
+                // The comparator of the options is not used.
+                let mut opt = Options::default();
+                opt.cache_index_and_filter_blocks = cache_index;
+                let table = Table::new(opt, wrap_buffer(src.clone()), size).unwrap();
+
+                let stats = table.stats().unwrap();
+                assert_eq!(stats.rocksdb_format_version, Some(5));
+                assert_eq!(stats.checksum_type, ctype);
+                assert_eq!(stats.data_blocks, 3);
+                assert_eq!(stats.compressed_data_blocks, 2);
+                assert_eq!(table.filter_name(), None);
+                assert_eq!(table.properties().unwrap().unwrap().num_entries, 7);
+                assert_eq!(table.verify(), Ok(7));
+
+                assert_eq!(
+                    table.get_at(b"abd", key_types::MAX_SEQUENCE_NUMBER),
+                    Ok(Some(LookupResult::Deleted { seq: 4 }))
+                );
+                assert_eq!(
+                    table.get_at(b"abd", 3),
+                    Ok(Some(LookupResult::Value {
+                        seq: 2,
+                        value: b"dee".to_vec()
+                    }))
+                );
+                assert_eq!(table.get_at(b"abd", 1), Ok(None));
+                assert_eq!(table.get_at(b"bcd", 4), Ok(None));
+                assert_eq!(
+                    table.get_at(b"zzz", 100),
+                    Ok(Some(LookupResult::Value {
+                        seq: 7,
+                        value: b"111".to_vec()
+                    }))
+                );
+                for missing in [&b"ab"[..], b"bz", b"zzzz"] {
+                    assert_eq!(table.get_at(missing, 100), Ok(None));
                 }
+                let (k, v) = &blocks[1][1];
+                assert_eq!(table.get(k), Ok(Some(v.clone())));
+
+                let mut iter = table.iter();
+                let entries: Vec<_> = SSIteratorIter::wrap(&mut iter).collect();
+                assert_eq!(entries, blocks.concat());
             }
-        }
-        // Reached in case of failure.
-        self.reset();
-    }
 
-    fn prev(&mut self) -> bool {
-        // happy path: current block contains previous entry
-        if let Some(ref mut cb) = self.current_block {
-            if cb.prev() {
-                return true;
+            if ctype != ChecksumType::NoChecksum {
+                let mut corrupted = src.clone();
+                corrupted[3] ^= 0x01;
+                let table = Table::new(Options::default(), wrap_buffer(corrupted), size).unwrap();
+                assert_eq!(table.verify().unwrap_err().code, StatusCode::Corruption);
             }
         }
 
-        // Go back one block and look for the last entry in the previous block
-        if self.index_block.prev() {
-            if let Some((_, handle)) = current_key_val(&self.index_block) {
-                if self.load_block(&handle).is_ok() {
-                    self.current_block.as_mut().unwrap().seek_to_last();
-                    self.current_block.as_ref().unwrap().valid()
-                } else {
-                    self.reset();
-                    false
-                }
-            } else {
-                false
-            }
-        } else {
-            false
+        // Only the bytewise comparator is supported.
+        let src = build_rocksdb_table(
+            &blocks,
+            ChecksumType::Crc32c,
+            &props("rocksdb.ReverseBytewiseComparator", false, false),
+            false,
+            false,
+        );
+        let size = src.len();
+        match Table::new(Options::default(), wrap_buffer(src), size) {
+            Err(e) => assert_eq!(e.code, StatusCode::NotSupported),
+            Ok(_) => panic!("table with unsupported comparator was opened"),
         }
     }
 
-    fn reset(&mut self) {
-        self.index_block.reset();
-        self.current_block = None;
+    /// Opens a table of testdata/rocksdb, written by RocksDB (see generate.cc there).
+    fn open_rocksdb_fixture(name: &str) -> Table {
+        let path = path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata/rocksdb")
+            .join(name);
+        Table::new_from_file(Options::default(), &path).unwrap()
     }
 
-    // This iterator is special in that it's valid even before the first call to advance(). It
-    // behaves correctly, though.
-    fn valid(&self) -> bool {
-        self.current_block.is_some() && (self.current_block.as_ref().unwrap().valid())
-    }
+    #[test]
+    fn test_table_rocksdb_fixtures() {
+        let key = |i: usize| format!("key{:03}", i).into_bytes();
+        let value = |i: usize| format!("value{}{}", i, "v".repeat(i % 20)).into_bytes();
+
+        for (version, ctype) in [
+            (2, ChecksumType::Crc32c),
+            (3, ChecksumType::XxHash),
+            (4, ChecksumType::XxHash64),
+            (5, ChecksumType::Xxh3),
+        ] {
+            let table = open_rocksdb_fixture(&format!("format_v{}.sst", version));
+            let stats = table.stats().unwrap();
+            assert_eq!(stats.rocksdb_format_version, Some(version));
+            assert_eq!(stats.checksum_type, ctype);
+            assert!(stats.data_blocks > 1);
+            assert_eq!(table.properties().unwrap().unwrap().num_entries, 200);
+            assert_eq!(table.verify(), Ok(200));
+
+            for i in 0..200 {
+                assert_eq!(
+                    table.get_at(&key(i), key_types::MAX_SEQUENCE_NUMBER),
+                    Ok(Some(LookupResult::Value {
+                        seq: 0,
+                        value: value(i)
+                    }))
+                );
+            }
+            assert_eq!(table.get_at(b"key200", 0), Ok(None));
 
-    fn current(&self, key: &mut Vec<u8>, val: &mut Vec<u8>) -> bool {
-        if let Some(ref cb) = self.current_block {
-            cb.current(key, val)
-        } else {
-            false
+            let mut iter = table.iter();
+            let entries: Vec<_> = SSIteratorIter::wrap(&mut iter).collect();
+            assert_eq!(entries.len(), 200);
+            assert_eq!(
+                entries[7].0,
+                key_types::build_internal_key(&key(7), 0, ValueType::TypeValue)
+            );
+            assert_eq!(entries[7].1, value(7));
         }
-    }
 
-    fn current_key(&self) -> Option<&[u8]> {
-        if let Some(ref cb) = self.current_block {
-            cb.current_key()
-        } else {
-            None
+        // key000..key049 put at sequence numbers 1 to 50, and [key010, key020) deleted at 51.
+        let table = open_rocksdb_fixture("range_del.sst");
+        assert_eq!(table.range_deletions().len(), 1);
+        for i in 0..50 {
+            let seq = i as u64 + 1;
+            let at_max = table.get_at(&key(i), key_types::MAX_SEQUENCE_NUMBER);
+            if (10..20).contains(&i) {
+                assert_eq!(at_max, Ok(Some(LookupResult::Deleted { seq: 51 })));
+            } else {
+                assert_eq!(
+                    at_max,
+                    Ok(Some(LookupResult::Value {
+                        seq,
+                        value: value(i)
+                    }))
+                );
+            }
+            assert_eq!(
+                table.get_at(&key(i), 50),
+                Ok(Some(LookupResult::Value {
+                    seq,
+                    value: value(i)
+                }))
+            );
         }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::options::CompressionType;
-    use crate::table_builder::TableBuilder;
-    use crate::test_util::{test_iterator_properties, SSIteratorIter};
-    use crate::types::{current_key_val, SSIterator};
-
-    use super::*;
+        assert_eq!(
+            table.get_at(b"key015x", 51),
+            Ok(Some(LookupResult::Deleted { seq: 51 }))
+        );
+        let ropt = ReadOptions {
+            hide_range_deletions: true,
+            ..ReadOptions::default()
+        };
+        let mut iter = table.iter_with_options(&ropt);
+        assert_eq!(SSIteratorIter::wrap(&mut iter).count(), 40);
 
-    const LOCK_POISONED: &str = "Lock poisoned";
+        // a: put, b: merge, c: single deletion, d: deletion, e: put.
+        let table = open_rocksdb_fixture("value_types.sst");
+        let max = key_types::MAX_SEQUENCE_NUMBER;
+        assert_eq!(
+            table.get_at(b"a", max),
+            Ok(Some(LookupResult::Value {
+                seq: 1,
+                value: b"put".to_vec()
+            }))
+        );
+        assert_eq!(
+            table.get_at(b"b", max).unwrap_err().code,
+            StatusCode::NotSupported
+        );
+        assert_eq!(table.get_at(b"b", 1), Ok(None));
+        assert_eq!(
+            table.get_at(b"c", max),
+            Ok(Some(LookupResult::Deleted { seq: 3 }))
+        );
+        assert_eq!(
+            table.get_at(b"d", max),
+            Ok(Some(LookupResult::Deleted { seq: 4 }))
+        );
+        assert_eq!(table.verify(), Ok(5));
 
-    fn build_data() -> Vec<(&'static str, &'static str)> {
-        vec![
-            // block 1
-            ("abc", "def"),
-            ("abd", "dee"),
-            ("bcd", "asa"),
-            // block 2
-            ("bsr", "a00"),
-            ("xyz", "xxx"),
-            ("xzz", "yyy"),
-            // block 3
-            ("zzz", "111"),
-        ]
+        // Neither plain lookups nor iterators return the raw value of the merge operand.
+        // "b" with sequence number 2 and RocksDB's merge value type.
+        let mut merge_key = b"b".to_vec();
+        merge_key.extend_from_slice(&(2u64 << 8 | 0x2).encode_fixed_vec());
+        assert_eq!(
+            table.get(&merge_key).unwrap_err().code,
+            StatusCode::NotSupported
+        );
+        let a = key_types::build_internal_key(b"a", 1, ValueType::TypeValue);
+        assert_eq!(table.get(&a), Ok(Some(b"put".to_vec())));
+        let mut iter = table.iter();
+        assert!(iter.advance());
+        assert!(!iter.advance());
+        assert_eq!(iter.status().unwrap_err().code, StatusCode::NotSupported);
     }
 
-    // Build a table containing raw keys (no format). It returns (vector, length) for convenience
-    // reason, a call f(v, v.len()) doesn't work for borrowing reasons.
-    fn build_table(data: Vec<(&'static str, &'static str)>) -> (Vec<u8>, usize) {
-        let mut d = Vec::with_capacity(512);
+    #[test]
+    fn test_table_range_deletions() {
         let mut opt = Options::default();
         opt.block_restart_interval = 2;
         opt.block_size = 32;
-        opt.compression_type = CompressionType::CompressionSnappy;
-
+        let mut src = vec![];
         {
-            // Uses the standard comparator in opt.
-            let mut b = TableBuilder::new(opt, &mut d);
-
-            for &(k, v) in data.iter() {
+            let mut b = TableBuilder::new(opt.clone(), &mut src);
+            b.delete_range(b"xz", b"zz");
+            for &(k, v) in build_data().iter() {
                 b.add(k.as_bytes(), v.as_bytes()).unwrap();
             }
-
+            b.delete_range(b"abd", b"bcd");
+            b.delete_range(b"b", b"bsr");
             b.finish().unwrap();
         }
+        let size = src.len();
+        let table = Table::new(opt, wrap_buffer(src), size).unwrap();
 
-        let size = d.len();
-        (d, size)
-    }
+        assert_eq!(
+            table.range_deletions(),
+            &[
+                (b"abd".to_vec(), b"bsr".to_vec()),
+                (b"xz".to_vec(), b"zz".to_vec())
+            ]
+        );
+        assert!(table
+            .meta_blocks()
+            .unwrap()
+            .iter()
+            .any(|m| m.name == RANGE_DELETIONS_BLOCK_KEY));
+        for (k, v) in build_data() {
+            let expected = match k {
+                "abd" | "bcd" | "xzz" => None,
+                _ => Some(v.as_bytes().to_vec()),
+            };
+            assert_eq!(table.get(k.as_bytes()), Ok(expected));
+        }
 
-    fn wrap_buffer(src: Vec<u8>) -> Box<dyn RandomAccess> {
-        Box::new(src)
+        // Iterators only hide deleted keys if asked to, while lookups always do.
+        let ropt = ReadOptions {
+            hide_range_deletions: false,
+            ..ReadOptions::default()
+        };
+        assert_eq!(table.get_with_options(b"abd", &ropt), Ok(None));
+        let mut iter = table.iter_with_options(&ropt);
+        assert_eq!(SSIteratorIter::wrap(&mut iter).count(), build_data().len());
+        iter.seek(b"abd");
+        assert_eq!(iter.current_key(), Some(&b"abd"[..]));
+
+        let ropt = ReadOptions {
+            hide_range_deletions: true,
+            ..ReadOptions::default()
+        };
+        let mut iter = table.iter_with_options(&ropt);
+        let keys: Vec<_> = SSIteratorIter::wrap(&mut iter).map(|(k, _)| k).collect();
+        assert_eq!(
+            keys,
+            vec![
+                b"abc".to_vec(),
+                b"bsr".to_vec(),
+                b"xyz".to_vec(),
+                b"zzz".to_vec()
+            ]
+        );
+        test_iterator_properties(table.iter_with_options(&ropt));
+
+        let mut iter = table.iter_with_options(&ropt);
+        iter.seek(b"abd");
+        assert_eq!(iter.current_key(), Some(&b"bsr"[..]));
+        iter.seek(b"xz");
+        assert_eq!(iter.current_key(), Some(&b"zzz"[..]));
+        assert!(iter.prev());
+        assert_eq!(iter.current_key(), Some(&b"xyz"[..]));
+        assert!(iter.prev());
+        assert!(iter.prev());
+        assert_eq!(iter.current_key(), Some(&b"abc"[..]));
+        assert!(!iter.prev());
+
+        let mut iter = table.iter_range_with_options(b"abd", b"zzz", &ropt);
+        let keys: Vec<_> = SSIteratorIter::wrap(&mut iter).map(|(k, _)| k).collect();
+        assert_eq!(keys, vec![b"bsr".to_vec(), b"xyz".to_vec()]);
     }
 
     #[test]
-    fn test_table_approximate_offset() {
-        let (src, size) = build_table(build_data());
-        let mut opt = Options::default();
-        opt.block_size = 32;
-        let table = Table::new(opt, wrap_buffer(src), size).unwrap();
-        let mut iter = table.iter();
+    fn test_table_compact_blocks() {
+        let data: Vec<(Vec<u8>, Vec<u8>)> = (0..500)
+            .map(|i| (format!("k{:05}", i).into_bytes(), vec![b'v'; i % 3]))
+            .collect();
+        let build = |opt: &Options| build_table_with(opt.clone(), &data).0;
 
-        let expected_offsets = vec![0, 0, 0, 44, 44, 44, 89];
-        let mut i = 0;
-        for (k, _) in SSIteratorIter::wrap(&mut iter) {
-            assert_eq!(expected_offsets[i], table.approx_offset_of(&k));
-            i += 1;
+        let mut opt = Options::default();
+        opt.block_size = 128;
+        opt.block_restart_interval = 1;
+        let standard = build(&opt);
+        opt.block_format = BlockFormat::Compact;
+        opt.index_type = IndexType::Partitioned;
+        let compact = build(&opt);
+        assert!(compact.len() < standard.len());
+
+        // The block format is taken from the table.
+        let size = compact.len();
+        let table = Table::new(Options::default(), wrap_buffer(compact), size).unwrap();
+        assert!(table
+            .footer
+            .has_feature(table_builder::FEATURE_COMPACT_BLOCKS));
+        for (k, v) in data.iter() {
+            assert_eq!(table.get(k), Ok(Some(v.clone())));
         }
+        assert_eq!(table.get(b"k00010a"), Ok(None));
 
-        // Key-past-last returns offset of metaindex block.
-        assert_eq!(137, table.approx_offset_of("{aa".as_bytes()));
+        let mut iter = table.iter();
+        assert_eq!(SSIteratorIter::wrap(&mut iter).count(), data.len());
+        iter.seek(b"k00250");
+        assert!(iter.prev());
+        assert_eq!(iter.current_key(), Some(&b"k00249"[..]));
     }
 
     #[test]
-    fn test_table_block_cache_use() {
-        let (src, size) = build_table(build_data());
+    fn test_table_blob_values() {
+        let data: Vec<(Vec<u8>, Vec<u8>)> = (0..100)
+            .map(|i| {
+                (
+                    format!("key{:03}", i).into_bytes(),
+                    vec![b'a' + (i % 26) as u8; i],
+                )
+            })
+            .collect();
         let mut opt = Options::default();
-        opt.block_size = 32;
+        opt.block_size = 128;
+        opt.index_type = IndexType::Partitioned;
+        opt.blob_value_threshold = 50;
 
-        let table = Table::new(opt.clone(), wrap_buffer(src), size).unwrap();
+        let (mut src, mut blob_src) = (vec![], vec![]);
+        {
+            let mut b = TableBuilder::new_with_blob_file(opt.clone(), &mut src, &mut blob_src);
+            for (k, v) in data.iter() {
+                b.add(k, v).unwrap();
+            }
+            b.finish().unwrap();
+        }
+        assert_eq!(blob_src.len(), (51..100).map(|len| len + 4).sum::<usize>());
+
+        let size = src.len();
+        let table = Table::new_with_blob_file(
+            opt.clone(),
+            wrap_buffer(src.clone()),
+            size,
+            wrap_buffer(blob_src.clone()),
+        )
+        .unwrap();
+        for (k, v) in data.iter() {
+            assert_eq!(table.get(k), Ok(Some(v.clone())));
+        }
         let mut iter = table.iter();
+        assert_eq!(SSIteratorIter::wrap(&mut iter).collect::<Vec<_>>(), data);
 
-        // index/metaindex blocks are not cached. That'd be a waste of memory.
-        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 0);
+        // Without blob file, only inline values can be read.
+        let table = Table::new(opt.clone(), wrap_buffer(src.clone()), size).unwrap();
+        assert_eq!(table.get(b"key050"), Ok(Some(data[50].1.clone())));
+        assert_eq!(
+            table.get(b"key051").unwrap_err().code,
+            StatusCode::InvalidArgument
+        );
 
-        iter.next();
-        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 1);
+        // Blobs are checksummed.
+        blob_src[0] ^= 1;
+        let table =
+            Table::new_with_blob_file(opt, wrap_buffer(src), size, wrap_buffer(blob_src)).unwrap();
+        assert_eq!(
+            table.get(b"key051").unwrap_err().code,
+            StatusCode::Corruption
+        );
+        assert_eq!(table.get(b"key052"), Ok(Some(data[52].1.clone())));
 
-        // This may fail if block parameters or data change. In that case, adapt it.
-        iter.next();
-        iter.next();
-        iter.next();
-        iter.next();
-        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 2);
+        // Iterators stop at the broken blob and report the error.
+        let mut iter = table.iter();
+        assert_eq!(
+            SSIteratorIter::wrap(&mut iter).collect::<Vec<_>>(),
+            data[..51].to_vec()
+        );
+        assert!(!iter.valid());
+        assert!(!iter.advance());
+        assert_eq!(iter.status().unwrap_err().code, StatusCode::Corruption);
+
+        iter.seek(b"key052");
+        assert!(!iter.valid());
+        let mut iter = table.iter();
+        iter.seek(b"key052");
+        assert_eq!(current_key_val(&iter), Some(data[52].clone()));
+        assert!(!iter.prev());
+        assert_eq!(iter.status().unwrap_err().code, StatusCode::Corruption);
     }
 
     #[test]
-    fn test_table_block_tiny_cache() {
+    fn test_table_filter_stats() {
         let (src, size) = build_table(build_data());
-        // Create a table with no block cache
-        let mut opt = Options::default().with_cache_capacity(1);
-        opt.block_size = 32;
+        let table = Table::new(Options::default(), wrap_buffer(src), size).unwrap();
+        assert_eq!(table.filter_stats(), FilterStats::default());
 
-        let table = Table::new(opt.clone(), wrap_buffer(src), size).unwrap();
-        let mut iter = table.iter();
+        for (k, _) in build_data() {
+            assert!(table.get(k.as_bytes()).unwrap().is_some());
+        }
+        let stats = table.filter_stats();
+        assert_eq!(stats.checks, build_data().len() as u64);
+        assert_eq!(stats.negatives, 0);
+        assert_eq!(stats.false_positives, 0);
+        assert_eq!(stats.false_positive_rate(), 0.);
+
+        // Clones share the statistics. Keys past the last key don't need a filter check.
+        let clone = table.clone();
+        clone.reset_filter_stats();
+        let missing = ["aaa", "abe", "bbb", "bzz", "xyy", "zzy"];
+        for k in missing.iter() {
+            assert!(clone.get(k.as_bytes()).unwrap().is_none());
+        }
+        assert!(clone.get(b"zzzz").unwrap().is_none());
 
-        // index/metaindex blocks are not cached. That'd be a waste of memory.
+        let stats = table.filter_stats();
+        assert_eq!(stats.checks, missing.len() as u64);
+        assert_eq!(
+            stats.negatives + stats.false_positives,
+            missing.len() as u64
+        );
+        assert!(stats.negatives > 0);
+        assert_eq!(
+            stats.false_positive_rate(),
+            stats.false_positives as f64 / missing.len() as f64
+        );
+    }
+
+    #[test]
+    fn test_table_range_filter() {
+        // Keys "k000.a" ... "k098.j": only even prefixes are present.
+        let data: Vec<(Vec<u8>, Vec<u8>)> = (0..100)
+            .step_by(2)
+            .flat_map(|i| {
+                (b'a'..b'k').map(move |c| {
+                    (
+                        format!("k{:03}.{}", i, c as char).into_bytes(),
+                        format!("v{}", i).into_bytes(),
+                    )
+                })
+            })
+            .collect();
+        let mut opt = Options::default();
+        opt.block_size = 64;
+        opt.range_filter_prefix_len = 4;
+
+        let (src, size) = build_table_with(opt.clone(), &data);
+        let table = Table::new(opt.clone(), wrap_buffer(src.clone()), size).unwrap();
+
+        assert!(table.may_contain_range(b"k012.", b"k012/").unwrap());
+        assert!(table.may_contain_range(b"k012.c", b"k012.d").unwrap());
+        assert!(table.may_contain_range(b"k013", b"k014").unwrap());
+        assert!(!table.may_contain_range(b"k013.", b"k013/").unwrap());
+        assert!(!table.may_contain_range(b"k051.", b"k051/").unwrap());
+        assert!(!table.may_contain_range(b"l", b"m").unwrap());
+        assert!(!table.may_contain_range(b"k012/", b"k012.").unwrap());
+
+        let collect = |mut iter: TableIterator| {
+            SSIteratorIter::wrap(&mut iter)
+                .map(|(k, _)| k)
+                .collect::<Vec<_>>()
+        };
+        let expected = |start: &[u8], end: &[u8]| {
+            data.iter()
+                .filter(|(k, _)| k.as_slice() >= start && k.as_slice() < end)
+                .map(|(k, _)| k.clone())
+                .collect::<Vec<_>>()
+        };
+        for &(start, end) in &[
+            (&b"k012."[..], &b"k012/"[..]),
+            (b"k012.c", b"k012.f"),
+            (b"k013.", b"k013/"),
+            (b"k011", b"k017.b"),
+            (b"", b"k002.c"),
+            (b"k097", b"l"),
+        ] {
+            assert_eq!(collect(table.iter_range(start, end)), expected(start, end));
+        }
+
+        // No data block is read for a range ruled out by the range filter.
+        let opt = opt.with_cache_capacity(100);
+        let table = Table::new(opt.clone(), wrap_buffer(src), size).unwrap();
+        let mut iter = table.iter_range(b"k051.", b"k051/");
+        assert!(!iter.advance());
+        iter.seek(b"k051.c");
+        assert!(!iter.valid());
         assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 0);
 
-        // We should have at most one item in the cache
-        iter.next();
-        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 1);
-        iter.next();
-        assert_eq!(opt.block_cache.read().expect(LOCK_POISONED).count(), 1);
+        // Seeking and going backwards stays in range.
+        let mut iter = table.iter_range(b"k012.c", b"k012.f");
+        iter.seek(b"k000");
+        assert_eq!(iter.current_key(), Some(&b"k012.c"[..]));
+        assert!(!iter.prev());
+        iter.seek(b"k012.e");
+        assert!(!iter.advance());
     }
 
     #[test]
@@ -515,17 +3482,12 @@ mod tests {
         let (src, size) = build_table(build_data());
 
         let table = Table::new(Options::default(), wrap_buffer(src), size).unwrap();
-        assert!(table.filters.is_some());
-        let filter_reader = table.filters.clone().unwrap();
+        let filter_reader = table.filter_block().unwrap().unwrap();
         let mut iter = table.iter();
 
-        loop {
-            if let Some((k, _)) = iter.next() {
-                assert!(filter_reader.key_may_match(iter.current_block_off, &k));
-                assert!(!filter_reader.key_may_match(iter.current_block_off, b"somerandomkey"));
-            } else {
-                break;
-            }
+        while let Some((k, _)) = iter.next() {
+            assert!(filter_reader.key_may_match(iter.current_block_off, &k));
+            assert!(!filter_reader.key_may_match(iter.current_block_off, b"somerandomkey"));
         }
     }
 
@@ -628,6 +3590,13 @@ mod tests {
         assert!(!iter.valid());
         iter.seek(b"bbb");
         assert!(iter.valid());
+
+        // Seeking past the last key of a block continues in the next block.
+        iter.seek(b"bce");
+        assert_eq!(
+            current_key_val(&iter),
+            Some((b"bsr".to_vec(), b"a00".to_vec()))
+        );
     }
 
     #[test]
@@ -669,14 +3638,16 @@ mod tests {
 
         let table = Table::new(Options::default(), wrap_buffer(src), size).unwrap();
 
-        assert!(table.filters.is_some());
-        assert_eq!(table.filters.as_ref().unwrap().num(), 1);
+        let filters = table.filter_block().unwrap();
+        assert!(filters.is_some());
+        assert_eq!(filters.unwrap().num(), 1);
 
         {
             let mut _iter = table.iter();
             let iter = SSIteratorIter::wrap(&mut _iter);
             // first block is skipped
             assert_eq!(iter.count(), 4);
+            assert_eq!(_iter.status().unwrap_err().code, StatusCode::Corruption);
         }
 
         {
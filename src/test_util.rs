@@ -1,8 +1,11 @@
+use crate::blockhandle::BlockHandle;
 use crate::cmp::{Cmp, DefaultCmp};
 use crate::types::{current_key_val, SSIterator};
 
 use std::cmp::Ordering;
 
+use integer_encoding::{FixedInt, VarInt};
+
 /// TestSSIter is an SSIterator over a vector, to be used for testing purposes.
 pub struct TestSSIter<'a> {
     v: Vec<(&'a [u8], &'a [u8])>,
@@ -12,11 +15,11 @@ pub struct TestSSIter<'a> {
 
 impl<'a> TestSSIter<'a> {
     pub fn new(c: Vec<(&'a [u8], &'a [u8])>) -> TestSSIter<'a> {
-        return TestSSIter {
+        TestSSIter {
             v: c,
             ix: 0,
             init: false,
-        };
+        }
     }
 }
 
@@ -144,6 +147,51 @@ pub fn test_iterator_properties<It: SSIterator>(mut it: It) {
     assert!(!it.valid());
 }
 
+/// Encodes an index block the way RocksDB does (see `rocksdb::convert_index_block()`), with a
+/// restart point every `restart_interval` entries and optionally delta-encoded block handles.
+pub fn encode_rocksdb_index_block(
+    entries: &[(Vec<u8>, BlockHandle)],
+    restart_interval: usize,
+    delta: bool,
+) -> Vec<u8> {
+    let mut buf = vec![];
+    let mut restarts = vec![];
+    for (i, (key, handle)) in entries.iter().enumerate() {
+        let shared = if i % restart_interval == 0 {
+            restarts.push(buf.len() as u32);
+            0
+        } else {
+            let last = &entries[i - 1].0;
+            last.iter()
+                .zip(key.iter())
+                .take_while(|(a, b)| a == b)
+                .count()
+        };
+        let value = if delta && shared > 0 {
+            (handle.size() as i64 - entries[i - 1].1.size() as i64).encode_var_vec()
+        } else {
+            let mut value = handle.offset().encode_var_vec();
+            value.extend_from_slice(&handle.size().encode_var_vec());
+            value
+        };
+        buf.extend_from_slice(&shared.encode_var_vec());
+        buf.extend_from_slice(&(key.len() - shared).encode_var_vec());
+        if !delta {
+            buf.extend_from_slice(&value.len().encode_var_vec());
+        }
+        buf.extend_from_slice(&key[shared..]);
+        buf.extend_from_slice(&value);
+    }
+    if restarts.is_empty() {
+        restarts.push(0);
+    }
+    for restart in restarts.iter() {
+        buf.extend_from_slice(&restart.encode_fixed_vec());
+    }
+    buf.extend_from_slice(&(restarts.len() as u32).encode_fixed_vec());
+    buf
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
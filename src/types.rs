@@ -29,7 +29,7 @@ impl RandomAccess for BufferBackedFile {
         } else {
             dst.len()
         };
-        (&mut dst[0..to_read]).copy_from_slice(&self[off..off + to_read]);
+        dst[0..to_read].copy_from_slice(&self[off..off + to_read]);
         Ok(to_read)
     }
 }
@@ -86,6 +86,13 @@ pub trait SSIterator {
 
     // default implementations.
 
+    /// Returns the error that ended the iteration early, if any. An iterator that is not valid
+    /// has either reached the end, or this returns an error. Iterators that skip unreadable data
+    /// (like `TableIterator`) return its error here even while they are still valid.
+    fn status(&self) -> Result<()> {
+        Ok(())
+    }
+
     /// next is like Iterator::next(). It's implemented here because Rust disallows implementing a
     /// foreign trait for any type, thus we can't do `impl<T: SSIterator> Iterator<Item=Vec<u8>>
     /// for T {}`.
@@ -141,6 +148,9 @@ impl SSIterator for Box<dyn SSIterator> {
     fn prev(&mut self) -> bool {
         self.as_mut().prev()
     }
+    fn status(&self) -> Result<()> {
+        self.as_ref().status()
+    }
 }
 
 // Allow interface to iterator.
@@ -0,0 +1,114 @@
+// Writes the RocksDB test fixtures of the sstable crate (testdata/rocksdb), with RocksDB 8.1.1
+// built with Snappy:
+//
+//   g++ -std=c++17 -DOS_LINUX -DROCKSDB_PLATFORM_POSIX -DSNAPPY -I$ROCKSDB -I$ROCKSDB/include \
+//       generate.cc $ROCKSDB/librocksdb.a -lsnappy -lpthread -ldl -o generate
+//   ./generate /tmp/fixtures && cp /tmp/fixtures/*.sst testdata/rocksdb
+//
+// The directory must not contain the databases of a previous run.
+#include <cstdio>
+#include <fstream>
+#include <string>
+
+#include "rocksdb/db.h"
+#include "rocksdb/options.h"
+#include "rocksdb/sst_file_writer.h"
+#include "rocksdb/table.h"
+#include "rocksdb/utilities/options_util.h"
+#include "utilities/merge_operators.h"
+
+using namespace ROCKSDB_NAMESPACE;
+
+static void check(const Status& s) {
+  if (!s.ok()) {
+    fprintf(stderr, "%s\n", s.ToString().c_str());
+    exit(1);
+  }
+}
+
+static Options options(int format_version, ChecksumType checksum) {
+  BlockBasedTableOptions table;
+  table.format_version = format_version;
+  table.checksum = checksum;
+  table.block_size = 256;
+  Options opt;
+  opt.compression = kSnappyCompression;
+  opt.table_factory.reset(NewBlockBasedTableFactory(table));
+  return opt;
+}
+
+static std::string key(int i) {
+  char buf[16];
+  snprintf(buf, sizeof(buf), "key%03d", i);
+  return buf;
+}
+
+static std::string value(int i) {
+  return "value" + std::to_string(i) + std::string(i % 20, 'v');
+}
+
+// Opens a new database in `path`.
+static DB* open_db(const std::string& path) {
+  Options opt = options(5, kCRC32c);
+  opt.create_if_missing = true;
+  opt.error_if_exists = true;
+  opt.merge_operator = MergeOperators::CreateStringAppendOperator();
+  DB* db;
+  check(DB::Open(opt, path, &db));
+  return db;
+}
+
+// Flushes the memtable of `db` to a single table file, and copies it to `to`.
+static void flush_to(DB* db, const std::string& to) {
+  check(db->Flush(FlushOptions()));
+  std::vector<LiveFileMetaData> files;
+  db->GetLiveFilesMetaData(&files);
+  if (files.size() != 1) {
+    fprintf(stderr, "expected one table file\n");
+    exit(1);
+  }
+  std::ifstream src(files[0].db_path + files[0].name, std::ios::binary);
+  std::ofstream dst(to, std::ios::binary);
+  dst << src.rdbuf();
+}
+
+int main(int argc, char** argv) {
+  std::string dir = argv[1];
+  struct {
+    int format_version;
+    ChecksumType checksum;
+  } versions[] = {{2, kCRC32c}, {3, kxxHash}, {4, kxxHash64}, {5, kXXH3}};
+  for (auto v : versions) {
+    SstFileWriter w(EnvOptions(), options(v.format_version, v.checksum));
+    check(w.Open(dir + "/format_v" + std::to_string(v.format_version) + ".sst"));
+    for (int i = 0; i < 200; i++) {
+      check(w.Put(key(i), value(i)));
+    }
+    check(w.Finish());
+  }
+
+  {
+    // Flushed with a snapshot, so that the versions deleted by the range are kept.
+    DB* db = open_db(dir + "/range_del.db");
+    for (int i = 0; i < 50; i++) {
+      check(db->Put(WriteOptions(), key(i), value(i)));
+    }
+    const Snapshot* snapshot = db->GetSnapshot();
+    check(db->DeleteRange(WriteOptions(), db->DefaultColumnFamily(), key(10), key(20)));
+    flush_to(db, dir + "/range_del.sst");
+    db->ReleaseSnapshot(snapshot);
+    delete db;
+  }
+
+  {
+    DB* db = open_db(dir + "/value_types.db");
+    check(db->Put(WriteOptions(), "a", "put"));
+    check(db->Merge(WriteOptions(), "b", "merge"));
+    check(db->SingleDelete(WriteOptions(), "c"));
+    check(db->Delete(WriteOptions(), "d"));
+    check(db->Put(WriteOptions(), "e", "put"));
+    flush_to(db, dir + "/value_types.sst");
+    delete db;
+  }
+  return 0;
+}